@@ -0,0 +1,162 @@
+//! Helpers to stream folders and sets of loose files as a single tar archive.
+//!
+//! The wormhole protocol needs to know the size of an offer before any data is sent, so the archive is laid out up
+//! front (without reading any file contents) and then written out while it is being sent.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use async_tar::{Builder, EntryType, Header};
+use smol::fs::File;
+use smol::io::{AsyncReadExt, AsyncWrite};
+use smol::stream::StreamExt;
+
+/// Size of a tar block. Entry data is padded to a multiple of this.
+const BLOCK_SIZE: u64 = 512;
+
+/// Size of the name field of a tar header. Longer names need a GNU long name entry.
+const NAME_SIZE: u64 = 100;
+
+/// A file or folder to be added to an archive.
+pub(crate) struct ArchiveEntry {
+    /// The path of the file or folder on disk.
+    pub(crate) path: PathBuf,
+    /// The name of the file or folder inside the archive.
+    pub(crate) name: PathBuf,
+    /// Whether the entry is a folder rather than a file.
+    pub(crate) is_dir: bool,
+    /// The size of the file in bytes. Always zero for folders.
+    pub(crate) size: u64,
+    /// The offset within the archive at which the contents of the file start. Set by [`layout`].
+    pub(crate) offset: u64,
+}
+
+/// Returns a deterministic header for the given entry.
+fn header(entry: &ArchiveEntry) -> Header {
+    let mut header = Header::new_gnu();
+    if entry.is_dir {
+        header.set_entry_type(EntryType::Directory);
+        header.set_mode(0o755);
+    } else {
+        header.set_mode(0o644);
+    }
+    header.set_size(entry.size);
+    header.set_mtime(0);
+    header
+}
+
+/// Returns the given size, rounded up to a whole number of blocks.
+///
+/// # Arguments
+///
+/// * `size` - The size in bytes.
+fn padded_size(size: u64) -> u64 {
+    (size + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE
+}
+
+/// Returns the entries for all files and folders within a folder, with names relative to that folder.
+///
+/// Entries are sorted by name so that the archive is deterministic. Anything that is neither a file nor a folder (e.g.
+/// symbolic links) is skipped.
+///
+/// # Arguments
+///
+/// * `folder` - The folder to walk.
+pub(crate) async fn walk(folder: &Path) -> io::Result<Vec<ArchiveEntry>> {
+    let mut entries = Vec::new();
+    // Folders still to be walked, as (path on disk, name inside the archive).
+    let mut pending = vec![(folder.to_path_buf(), PathBuf::new())];
+    while let Some((path, name)) = pending.pop() {
+        let mut children = Vec::new();
+        let mut dir = smol::fs::read_dir(&path).await?;
+        while let Some(child) = dir.next().await {
+            let child = child?;
+            children.push((
+                child.path(),
+                name.join(child.file_name()),
+                child.file_type().await?,
+            ));
+        }
+        children.sort_by(|a, b| a.1.cmp(&b.1));
+
+        for (path, name, file_type) in children {
+            if file_type.is_dir() {
+                entries.push(ArchiveEntry {
+                    path: path.clone(),
+                    name: name.clone(),
+                    is_dir: true,
+                    size: 0,
+                    offset: 0,
+                });
+                pending.push((path, name));
+            } else if file_type.is_file() {
+                let size = smol::fs::metadata(&path).await?.len();
+                entries.push(ArchiveEntry {
+                    path,
+                    name,
+                    is_dir: false,
+                    size,
+                    offset: 0,
+                });
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Computes the offset of each entry's contents within the archive and returns the total size of the archive.
+///
+/// This is done arithmetically, mirroring what [`write`] writes, so that no file contents have to be read.
+///
+/// # Arguments
+///
+/// * `entries` - The entries of the archive, in the order they will be written.
+pub(crate) fn layout(entries: &mut [ArchiveEntry]) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in entries.iter_mut() {
+        // Names that don't fit into the header are preceded by a GNU long name entry, as written by the builder.
+        if header(entry).set_path(&entry.name).is_err() {
+            let name_size = entry.name.as_os_str().len() as u64;
+            if name_size < NAME_SIZE {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid name in archive: {}", entry.name.display()),
+                ));
+            }
+            // The name is followed by a terminating NUL byte.
+            size += BLOCK_SIZE + padded_size(name_size + 1);
+        }
+        size += BLOCK_SIZE;
+        entry.offset = size;
+        size += padded_size(entry.size);
+    }
+    // The archive ends with two empty blocks.
+    Ok(size + 2 * BLOCK_SIZE)
+}
+
+/// Writes the archive into the given writer.
+///
+/// # Arguments
+///
+/// * `entries` - The entries of the archive, as passed to [`layout`].
+/// * `writer` - The destination of the archive.
+pub(crate) async fn write<W>(entries: &[ArchiveEntry], writer: W) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin + Send + Sync,
+{
+    let mut builder = Builder::new(writer);
+    for entry in entries {
+        if entry.is_dir {
+            builder
+                .append_data(&mut header(entry), &entry.name, smol::io::empty())
+                .await?;
+        } else {
+            // We only send as many bytes as were advertised, in case the file grew in the meantime.
+            let file = File::open(&entry.path).await?.take(entry.size);
+            builder
+                .append_data(&mut header(entry), &entry.name, file)
+                .await?;
+        }
+    }
+    builder.finish().await
+}
@@ -0,0 +1,301 @@
+//! Validation and completion of wormhole codes, e.g. for a UI in which the code is typed in.
+//!
+//! A code consists of a nameplate, which is the number of a mailbox on the rendezvous server, followed by a dash and
+//! a password that the key exchange between the Pylons is based on. The password of a code generated by
+//! [`crate::Pylon::gen_code`] consists of words of the Pylon's [`Wordlist`]. The helpers here must be given the same
+//! wordlist as the Pylon that generated the code.
+//!
+//! Anyone who guesses the password before the receiver Pylon joins the wormhole can impersonate it, so codes that don't
+//! come from [`crate::Pylon::gen_code`] are held to a minimum entropy.
+
+use crate::consts::MIN_CODE_ENTROPY;
+use crate::wordlist::Wordlist;
+use crate::PylonError;
+
+/// The maximum edit distance between a misspelled word and the words suggested for it.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// The maximum number of words suggested for a misspelled word.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Returns the error for a malformed code.
+///
+/// # Arguments
+///
+/// * `reason` - What is wrong with the code.
+fn invalid(reason: &str) -> PylonError {
+    PylonError::InvalidCode(reason.into())
+}
+
+/// Splits a code into its nameplate and password, failing if either is malformed.
+///
+/// # Arguments
+///
+/// * `code` - The code to split.
+pub(crate) fn split(code: &str) -> Result<(&str, &str), PylonError> {
+    let (nameplate, password) = code
+        .split_once('-')
+        .ok_or_else(|| invalid("expected a nameplate and a password separated by a dash"))?;
+    if nameplate.is_empty() || !nameplate.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid("the nameplate must be a number"));
+    }
+    if password.is_empty() || password.split('-').any(str::is_empty) {
+        return Err(invalid(
+            "the password must not be empty, nor contain empty words",
+        ));
+    }
+    if password
+        .chars()
+        .any(|c| c.is_whitespace() || c.is_control())
+    {
+        return Err(invalid("the password must not contain whitespace"));
+    }
+
+    Ok((nameplate, password))
+}
+
+/// Returns the number of characters of a word that can't be predicted from the ones before them.
+///
+/// Once the word starts repeating itself, e.g. `abcabc`, the rest of it is predictable. Before that, a character is
+/// predictable if it repeats the previous one, e.g. `aaaa`, or continues a sequence with a constant step, e.g. `12345`
+/// or `acegi`.
+///
+/// # Arguments
+///
+/// * `word` - The word.
+fn unpredictable_chars(word: &str) -> usize {
+    let chars: Vec<i64> = word.chars().map(|c| i64::from(u32::from(c))).collect();
+    let period = (1..chars.len())
+        .find(|&p| (p..chars.len()).all(|i| chars[i] == chars[i - p]))
+        .unwrap_or(chars.len());
+    (0..period)
+        .filter(|&i| match i {
+            0 => true,
+            1 => chars[1] != chars[0],
+            _ => chars[i] - chars[i - 1] != chars[i - 1] - chars[i - 2],
+        })
+        .count()
+}
+
+/// Returns the estimated entropy of a password, in bits.
+///
+/// Each word of the wordlist counts for the bits it is chosen with at its position. Any other word counts for the bits
+/// needed to pick a character from the character classes it uses, for each of its characters that can't be predicted
+/// from the ones before them, see [`unpredictable_chars`].
+///
+/// # Arguments
+///
+/// * `password` - The password, as returned by [`split`].
+/// * `wordlist` - The wordlist of the Pylon.
+pub(crate) fn entropy(password: &str, wordlist: &dyn Wordlist) -> f64 {
+    password
+        .split('-')
+        .enumerate()
+        .map(|(position, word)| {
+            let words = wordlist.words(position);
+            if words.contains(&word) {
+                return (words.len() as f64).log2();
+            }
+            let mut pool = 0;
+            if word.chars().any(|c| c.is_ascii_lowercase()) {
+                pool += 26;
+            }
+            if word.chars().any(|c| c.is_ascii_uppercase()) {
+                pool += 26;
+            }
+            if word.chars().any(|c| c.is_ascii_digit()) {
+                pool += 10;
+            }
+            if word.chars().any(|c| !c.is_ascii_alphanumeric()) {
+                // Punctuation and any other characters.
+                pool += 33;
+            }
+            unpredictable_chars(word) as f64 * f64::from(pool).log2()
+        })
+        .sum()
+}
+
+/// Validates a code agreed on beforehand, failing if it is malformed or too easy to guess.
+///
+/// # Arguments
+///
+/// * `code` - The code to validate.
+/// * `wordlist` - The wordlist of the Pylon.
+pub(crate) fn validate_agreed(code: &str, wordlist: &dyn Wordlist) -> Result<(), PylonError> {
+    let (_, password) = split(code)?;
+    let entropy = entropy(password, wordlist);
+    match entropy < MIN_CODE_ENTROPY {
+        true => Err(PylonError::WeakCode { entropy }),
+        false => Ok(()),
+    }
+}
+
+/// Returns the edit distance between two words, counting insertions, deletions, substitutions and transpositions of
+/// adjacent characters.
+///
+/// # Arguments
+///
+/// * `a` - The first word.
+/// * `b` - The second word.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // `d[i][j]` is the distance between the first `i` characters of `a` and the first `j` characters of `b`.
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Returns the words of the wordlist that the given word may be a misspelling of, closest first.
+///
+/// Only words that are at most two edits away are suggested, where an edit is the insertion, deletion or substitution
+/// of a character, or the transposition of two adjacent ones.
+///
+/// # Arguments
+///
+/// * `word` - The misspelled word.
+/// * `position` - The position of the word within the password of the code, starting from 0.
+/// * `wordlist` - The wordlist the code was generated with.
+pub fn suggest(word: &str, position: usize, wordlist: &dyn Wordlist) -> Vec<String> {
+    let word = wordlist.normalize(word);
+    let mut suggestions: Vec<(usize, &str)> = wordlist
+        .words(position)
+        .iter()
+        .map(|candidate| (distance(&word, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .collect();
+    suggestions.sort_unstable();
+    suggestions
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, word)| word.to_string())
+        .collect()
+}
+
+/// Validates the syntax of a code generated by [`crate::Pylon::gen_code`], without contacting the rendezvous server.
+///
+/// Fails with [`PylonError::InvalidCode`] if the nameplate isn't a number or if the password doesn't consist of
+/// `code_length` words, and with [`PylonError::UnknownCodeWord`] along with suggestions if a word isn't one that could
+/// have been generated at its position.
+///
+/// # Arguments
+///
+/// * `code` - The code to validate, as normalized by [`normalize`].
+/// * `code_length` - The length the code was generated with.
+/// * `wordlist` - The wordlist the code was generated with.
+pub fn validate(code: &str, code_length: usize, wordlist: &dyn Wordlist) -> Result<(), PylonError> {
+    let (_, password) = split(code)?;
+    let words: Vec<&str> = password.split('-').collect();
+    if words.len() != code_length {
+        return Err(PylonError::InvalidCode(
+            format!("expected {} words, but got {}", code_length, words.len()).into(),
+        ));
+    }
+    for (position, word) in words.into_iter().enumerate() {
+        if !wordlist.words(position).contains(&word) {
+            return Err(PylonError::UnknownCodeWord {
+                word: word.into(),
+                suggestions: suggest(word, position, wordlist),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the possible completions of a partially typed code, in order.
+///
+/// Until the first dash is typed, the nameplate is completed against the given nameplates. After that, the word being
+/// typed is completed against the words of the wordlist that could have been generated at its position. Completions
+/// end with a dash if more words are to follow.
+///
+/// # Arguments
+///
+/// * `prefix` - The partially typed code.
+/// * `code_length` - The length the code was generated with.
+/// * `nameplates` - The nameplates to complete against, e.g. as returned by [`crate::Pylon::list_nameplates`].
+/// * `wordlist` - The wordlist the code was generated with.
+pub fn complete(
+    prefix: &str,
+    code_length: usize,
+    nameplates: &[String],
+    wordlist: &dyn Wordlist,
+) -> Vec<String> {
+    let (typed, partial) = match prefix.rsplit_once('-') {
+        Some(parts) => parts,
+        None => {
+            let mut completions: Vec<&String> = nameplates
+                .iter()
+                .filter(|nameplate| nameplate.starts_with(prefix))
+                .collect();
+            completions.sort_unstable_by_key(|nameplate| (nameplate.len(), *nameplate));
+            return completions
+                .into_iter()
+                .map(|nameplate| format!("{}-", nameplate))
+                .collect();
+        }
+    };
+    let position = typed.matches('-').count();
+    if position >= code_length {
+        return Vec::new();
+    }
+    let separator = match position + 1 < code_length {
+        true => "-",
+        false => "",
+    };
+    let partial = wordlist.normalize(partial);
+    let mut completions: Vec<String> = wordlist
+        .words(position)
+        .iter()
+        .filter(|word| word.starts_with(&partial))
+        .map(|word| format!("{}-{}{}", typed, word, separator))
+        .collect();
+    completions.sort_unstable();
+    completions
+}
+
+/// Returns the code with each word of its password replaced by its normalized form, if that is part of the wordlist.
+///
+/// Words that aren't part of the wordlist even when normalized, e.g. those of a code agreed on beforehand, are kept as
+/// typed, since the password is case-sensitive. Whitespace around the code and within the nameplate is removed.
+///
+/// # Arguments
+///
+/// * `code` - The typed code.
+/// * `wordlist` - The wordlist the code was generated with.
+pub fn normalize(code: &str, wordlist: &dyn Wordlist) -> String {
+    let code = code.trim();
+    let (nameplate, password) = match code.split_once('-') {
+        Some(parts) => parts,
+        None => return code.to_string(),
+    };
+    let words: Vec<String> = password
+        .split('-')
+        .enumerate()
+        .map(|(position, word)| {
+            let normalized = wordlist.normalize(word);
+            match wordlist.words(position).contains(&normalized.as_str()) {
+                true => normalized,
+                false => word.to_string(),
+            }
+        })
+        .collect();
+
+    let nameplate: String = nameplate.split_whitespace().collect();
+
+    format!("{}-{}", nameplate, words.join("-"))
+}
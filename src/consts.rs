@@ -1,2 +1,9 @@
 /// The current version of the library.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The minimum estimated entropy, in bits, of the password of a code agreed on beforehand, see
+/// [`crate::Pylon::use_code`]. This matches a generated code of two words.
+pub const MIN_CODE_ENTROPY: f64 = 16.0;
+
+/// The version of the capability document that a Pylon exchanges with its peer, see [`crate::Capabilities`].
+pub const CAPABILITIES_VERSION: u32 = 1;
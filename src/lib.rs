@@ -4,32 +4,62 @@
 //!
 //! [`magic-wormhole`]: https://crates.io/crates/magic-wormhole
 
+mod archive;
+pub mod code;
 pub mod consts;
+mod mailbox;
+#[cfg(feature = "nameplates")]
+mod nameplates;
+mod paths;
+mod protocol;
+mod resume;
+pub mod uri;
+pub mod verifier;
+pub mod wordlist;
 
 use std::borrow::Cow;
+use std::cell::Cell;
 use std::error::Error;
 use std::future::Future;
 use std::net::SocketAddr;
-use std::path::Path;
-use std::pin::Pin;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use derive_builder::Builder;
 use magic_wormhole::rendezvous::DEFAULT_RENDEZVOUS_SERVER;
-use magic_wormhole::transfer::{self, AppVersion, ReceiveRequest, TransferError};
+use magic_wormhole::transfer::TransferError;
 pub use magic_wormhole::transit::TransitInfo;
-use magic_wormhole::transit::{self, RelayHint, RelayHintParseError, DEFAULT_RELAY_SERVER};
+use magic_wormhole::transit::{
+    self, RelayHint, RelayHintParseError, TransitConnector, DEFAULT_RELAY_SERVER,
+};
 use magic_wormhole::{AppConfig, AppID, Code, Wormhole, WormholeError};
+use piper::Writer;
 use serde::Serialize;
+use smol::channel::{Receiver, Sender};
 use smol::fs::File;
+use smol::io::{AsyncRead, AsyncSeek, AsyncSeekExt, AsyncWrite, SeekFrom};
+use smol::stream::{Stream, StreamExt};
+use smol::Timer;
 use thiserror::Error;
-use url::ParseError;
+use url::{ParseError, Url};
 
-/// Awaitable object that will perform the client-client handshake and yield the wormhole object on success.
-type Handshake = dyn Future<Output = Result<Wormhole, WormholeError>> + Unpin + Send + Sync;
+use crate::verifier::Verifier;
+use crate::wordlist::{PgpWordlist, Wordlist};
 
 /// Type alias for magic-wormhole transit abilities.
 pub type Abilities = transit::Abilities;
 
+/// Capacity (in bytes) of the in-memory pipes used to stream folder archives.
+const FOLDER_PIPE_CAPACITY: usize = 64 * 1024;
+
+/// Extension appended to the name of a file while it is being received.
+const PART_EXTENSION: &str = ".part";
+
+/// Name under which a batch of files is offered to the receiver Pylon.
+const BATCH_FOLDER_NAME: &str = "pylon-batch";
+
 /// Custom error type for the various errors a Pylon may encounter.
 ///
 /// These could be errors generated by the underlying wormhole library (some of which we handle explicitly and some of
@@ -49,6 +79,9 @@ pub enum PylonError {
         #[source]
         RelayHintParseError,
     ),
+    /// A relay server was configured without any URLs.
+    #[error("Relay server {0} has no URLs")]
+    InvalidRelay(Box<str>),
     /// Error parsing a URL. Eg: rendezvous server URL or relay server URL.
     /// This is just a wrapper over the `url` library's `ParseError`.
     #[error(transparent)]
@@ -56,33 +89,786 @@ pub enum PylonError {
     /// Error occurred during the transfer.
     /// This is just a wrapper over the underlying wormhole library's error of the same name.
     #[error("Error occurred during transfer")]
-    TransferError(
-        #[from]
-        #[source]
-        TransferError,
-    ),
+    TransferError(#[source] Box<TransferError>),
+    /// The received data doesn't match the data that was sent, as determined by comparing their checksums.
+    #[error("The received data doesn't match the sent data")]
+    IntegrityError,
     /// An error occurred with the underlying wormhole library that we aren't explicitly matching against.
     #[error(transparent)]
-    InternalError(#[from] WormholeError),
+    InternalError(Box<WormholeError>),
     /// An error occurred with building the Pylon.
     /// This is just a wrapper to allow easy propagation of builder errors with the `?` operator.
     #[error(transparent)]
     BuilderError(#[from] PylonBuilderError),
-    /// Generic errors.
-    #[error("An error occurred: {0}")]
-    Error(
+    /// The sender Pylon offered a name that would be written outside of the destination folder.
+    #[error("Refusing to write outside of the destination folder: {0}")]
+    PathTraversal(Box<str>),
+    /// The destination of a received file or folder already exists and the collision policy forbids replacing it.
+    #[error("Destination already exists: {0}")]
+    DestinationExists(Box<str>),
+    /// The Pylon is not in the state required by the requested operation.
+    #[error("Invalid Pylon state: expected {expected:?}, but was {actual:?}")]
+    InvalidState {
+        /// The state the Pylon must be in for the operation.
+        expected: PylonState,
+        /// The state the Pylon is actually in.
+        actual: PylonState,
+    },
+    /// The transfer was cancelled through its [`PylonCancelToken`].
+    #[error("The transfer was cancelled")]
+    Cancelled,
+    /// A phase of the transfer took longer than allowed by the Pylon's [`Timeouts`].
+    #[error("Timed out during the {phase:?} phase")]
+    Timeout {
+        /// The phase that timed out.
+        phase: TransferPhase,
+    },
+    /// The code expired before the receiver Pylon joined the wormhole, see [`PylonBuilder::code_expiry`].
+    #[error("The wormhole code expired")]
+    CodeExpired,
+    /// A wormhole code is malformed, see [`Pylon::use_code`] and [`code::validate`].
+    #[error("Invalid wormhole code: {0}")]
+    InvalidCode(Box<str>),
+    /// A word of a wormhole code isn't one that could have been generated at its position, see [`code::validate`].
+    #[error("Unknown word in wormhole code: {word}")]
+    UnknownCodeWord {
+        /// The unknown word.
+        word: Box<str>,
+        /// The words it may be a misspelling of, closest first.
+        suggestions: Vec<String>,
+    },
+    /// A wormhole code agreed on beforehand is too easy to guess, see [`Pylon::use_code`].
+    #[error("The wormhole code is too easy to guess ({entropy:.1} bits of entropy)")]
+    WeakCode {
+        /// The estimated entropy of the password of the code, in bits.
+        entropy: f64,
+    },
+    /// A `wormhole-transfer:` URI could not be parsed, see [`uri::CodeUri`].
+    /// This is just a wrapper over the underlying wormhole library's error of the same name.
+    #[error("Error parsing wormhole URI")]
+    UriParseError(
+        #[from]
+        #[source]
+        magic_wormhole::uri::ParseError,
+    ),
+    /// A QR code could not be rendered, e.g. because the data is too long.
+    #[error("Error rendering QR code: {0}")]
+    QrCode(#[from] qrcode::types::QrError),
+    /// The peer Pylon rejected the transfer.
+    #[error("The transfer was rejected by the peer")]
+    Rejected,
+    /// The verifier of the wormhole was rejected through a [`PylonConfirmToken`], by either Pylon.
+    #[error("The verifier of the wormhole was rejected")]
+    VerifierRejected,
+    /// The active transfer request is for a different kind of offer than the operation handles.
+    #[error("Expected a {expected:?} offer, but got a {actual:?} offer")]
+    UnexpectedOffer {
+        /// The kind of offer the operation handles.
+        expected: OfferKind,
+        /// The kind of offer that is actually pending.
+        actual: OfferKind,
+    },
+    /// The given path has no usable file or folder name.
+    #[error("Invalid path: {0}")]
+    InvalidPath(Box<str>),
+    /// The given path was expected to be a file, but isn't.
+    #[error("Not a file: {0}")]
+    NotAFile(Box<str>),
+    /// The given path was expected to be a folder, but isn't.
+    #[error("Not a folder: {0}")]
+    NotAFolder(Box<str>),
+    /// A file with the same name is already part of the batch transfer.
+    #[error("A file named {0} is already part of the batch")]
+    DuplicateName(Box<str>),
+    /// None of the files of a batch transfer can be sent.
+    #[error("None of the files can be sent")]
+    EmptyBatch,
+    /// A file of a batch transfer was not sent, because the transfer failed before reaching it.
+    #[error("The batch transfer failed before this file was sent")]
+    BatchAborted,
+    /// An I/O error occurred, e.g. while reading the files to send or writing the received ones.
+    #[error("I/O error")]
+    Io(
         #[from]
         #[source]
-        Box<dyn Error + Send + Sync>,
+        std::io::Error,
     ),
 }
 
+impl PylonError {
+    /// Returns a stable, machine-readable code for the kind of this error.
+    ///
+    /// Unlike the error message, the code doesn't change between versions, so it can be matched against by e.g. a UI.
+    pub fn code(&self) -> &'static str {
+        match self {
+            PylonError::CodegenError(_) => "codegen_failed",
+            PylonError::RelayHintParseError(_) | PylonError::InvalidRelay(_) => "relay_url_invalid",
+            PylonError::UrlParseError(_) => "url_invalid",
+            PylonError::TransferError(error) => match **error {
+                TransferError::PeerError(_) => "peer_error",
+                TransferError::TransitConnect(_) => "transit_failed",
+                TransferError::UnsupportedOffer => "offer_unsupported",
+                _ => "transfer_failed",
+            },
+            PylonError::IntegrityError => "integrity",
+            PylonError::InternalError(error) => match **error {
+                WormholeError::PakeFailed => "code_mismatch",
+                WormholeError::ServerError(_) => "rendezvous_failed",
+                _ => "wormhole_failed",
+            },
+            PylonError::BuilderError(_) => "builder",
+            PylonError::PathTraversal(_) => "path_traversal",
+            PylonError::DestinationExists(_) => "destination_exists",
+            PylonError::InvalidState { .. } => "invalid_state",
+            PylonError::Cancelled => "cancelled",
+            PylonError::Timeout { .. } => "timeout",
+            PylonError::CodeExpired => "code_expired",
+            PylonError::InvalidCode(_) => "code_invalid",
+            PylonError::UnknownCodeWord { .. } => "code_unknown_word",
+            PylonError::UriParseError(_) => "uri_invalid",
+            PylonError::QrCode(_) => "qr_failed",
+            PylonError::WeakCode { .. } => "code_weak",
+            PylonError::Rejected => "peer_rejected",
+            PylonError::VerifierRejected => "verifier_rejected",
+            PylonError::UnexpectedOffer { .. } => "unexpected_offer",
+            PylonError::InvalidPath(_) => "path_invalid",
+            PylonError::NotAFile(_) => "not_a_file",
+            PylonError::NotAFolder(_) => "not_a_folder",
+            PylonError::DuplicateName(_) => "duplicate_name",
+            PylonError::EmptyBatch => "empty_batch",
+            PylonError::BatchAborted => "batch_aborted",
+            PylonError::Io(_) => "io",
+        }
+    }
+
+    /// Returns whether the failed operation may succeed if it is tried again as is.
+    ///
+    /// This is the case for errors caused by the network or the servers, as opposed to e.g. invalid input.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            PylonError::TransferError(error) => !matches!(
+                **error,
+                TransferError::UnsupportedOffer | TransferError::PeerError(_)
+            ),
+            PylonError::InternalError(error) => {
+                matches!(**error, WormholeError::ServerError(_))
+            }
+            PylonError::IntegrityError => true,
+            PylonError::Timeout { phase } => {
+                matches!(phase, TransferPhase::Rendezvous | TransferPhase::Transit)
+            }
+            PylonError::Io(error) => matches!(
+                error.kind(),
+                std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::ConnectionRefused
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof
+            ),
+            _ => false,
+        }
+    }
+}
+
+impl From<TransferError> for PylonError {
+    fn from(error: TransferError) -> Self {
+        match error {
+            TransferError::Checksum => PylonError::IntegrityError,
+            TransferError::PeerError(error) if error == protocol::REJECTED => PylonError::Rejected,
+            TransferError::PeerError(error) if error == protocol::VERIFIER_REJECTED => {
+                PylonError::VerifierRejected
+            }
+            TransferError::Wormhole(error) => error.into(),
+            TransferError::IO(error) => match protocol::Elapsed::phase(&error) {
+                Some(phase) => PylonError::Timeout { phase },
+                None => PylonError::Io(error),
+            },
+            error => PylonError::TransferError(Box::new(error)),
+        }
+    }
+}
+
+impl From<WormholeError> for PylonError {
+    fn from(error: WormholeError) -> Self {
+        PylonError::InternalError(Box::new(error))
+    }
+}
+
 impl Serialize for PylonError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(self.to_string().as_ref())
+        ErrorPayload::from(self).serialize(serializer)
+    }
+}
+
+/// The serialized form of a [`PylonError`], e.g. for passing it on to a UI.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorPayload {
+    /// The machine-readable code of the error, as returned by [`PylonError::code`].
+    pub code: &'static str,
+    /// The human-readable error message.
+    pub message: String,
+    /// Whether the failed operation may succeed if it is tried again, as returned by [`PylonError::is_retryable`].
+    pub retryable: bool,
+    /// The messages of the errors that caused this error, from the most to the least direct cause.
+    pub sources: Vec<String>,
+}
+
+impl From<&PylonError> for ErrorPayload {
+    fn from(error: &PylonError) -> Self {
+        let mut sources = Vec::new();
+        let mut source = error.source();
+        while let Some(cause) = source {
+            sources.push(cause.to_string());
+            source = cause.source();
+        }
+
+        ErrorPayload {
+            code: error.code(),
+            message: error.to_string(),
+            retryable: error.is_retryable(),
+            sources,
+        }
+    }
+}
+
+/// The outcome of a completed transfer.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferReport {
+    /// The path of the received file or folder, if it was received into the filesystem.
+    pub path: Option<PathBuf>,
+    /// The hex-encoded SHA-256 checksum of the transferred data. For folders, this is the checksum of their archive.
+    pub sha256: String,
+    /// The number of bytes that went over the network. This is less than the size of the data if the transfer was
+    /// resumed.
+    pub bytes: u64,
+    /// The time it took to transfer the data, from establishing the transit connection until the data was confirmed.
+    #[serde(rename = "durationMs", serialize_with = "serialize_millis")]
+    pub duration: Duration,
+    /// The connection to the peer Pylon that the data was transferred over.
+    pub peer: PeerInfo,
+}
+
+impl TransferReport {
+    /// Creates the report of a completed transfer.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the received file or folder, if any.
+    /// * `transferred` - What was transferred.
+    fn new(path: Option<PathBuf>, transferred: protocol::Transferred) -> Self {
+        TransferReport {
+            path,
+            sha256: hex::encode(transferred.sha256),
+            bytes: transferred.bytes,
+            duration: transferred.duration,
+            peer: PeerInfo {
+                transit: transferred.transit,
+                addr: transferred.addr,
+            },
+        }
+    }
+}
+
+/// The transit connection to the peer Pylon that a transfer went over.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerInfo {
+    /// Whether the connection was direct or went through a relay server.
+    #[serde(serialize_with = "serialize_transit_info")]
+    pub transit: TransitInfo,
+    /// The socket address of the connection.
+    pub addr: SocketAddr,
+}
+
+/// What a Pylon supports, as exchanged with the peer Pylon during the key exchange, see [`Pylon::peer_capabilities`].
+///
+/// Features that the peer Pylon doesn't list are to be negotiated down, e.g. with [`Capabilities::common`], rather
+/// than used regardless. Other wormhole clients, and Pylons that predate the capability document, are reported with
+/// version 0 and the capabilities that they are known to have.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    /// The version of the capability document, see [`consts::CAPABILITIES_VERSION`].
+    pub version: u32,
+    /// The version of libpylon, see [`consts::VERSION`], if known.
+    pub libpylon: Option<String>,
+    /// The kinds of offers of the file transfer protocol that are supported: `message`, `file` and `directory`.
+    pub offers: Vec<String>,
+    /// The compression algorithms that are supported for the payload.
+    pub compression: Vec<String>,
+    /// The algorithms of the checksums that are exchanged after a file, e.g. `sha256`.
+    pub checksums: Vec<String>,
+    /// Whether interrupted file transfers can be resumed.
+    pub resume: bool,
+}
+
+impl Capabilities {
+    /// Returns the capabilities that both these and the given capabilities have, e.g. to negotiate ours down to those
+    /// of the peer Pylon.
+    ///
+    /// The version is the lower of both, and the lists keep the order of these capabilities, so that the first entry
+    /// is the preferred one. The libpylon version is only kept if both are the same.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other capabilities.
+    pub fn common(&self, other: &Capabilities) -> Capabilities {
+        let common = |ours: &[String], theirs: &[String]| {
+            ours.iter()
+                .filter(|entry| theirs.contains(entry))
+                .cloned()
+                .collect()
+        };
+
+        Capabilities {
+            version: self.version.min(other.version),
+            libpylon: self
+                .libpylon
+                .clone()
+                .filter(|_| self.libpylon == other.libpylon),
+            offers: common(&self.offers, &other.offers),
+            compression: common(&self.compression, &other.compression),
+            checksums: common(&self.checksums, &other.checksums),
+            resume: self.resume && other.resume,
+        }
+    }
+}
+
+/// The outcome of sending a single file as part of a batch transfer.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchFileResult {
+    /// The path of the file, as passed to [`Pylon::start_batch_transfer`].
+    pub path: PathBuf,
+    /// The number of bytes of the file that were sent.
+    pub bytes_sent: u64,
+    /// The reason the file could not be sent, if it wasn't.
+    pub error: Option<PylonError>,
+}
+
+/// The kind of an offer made by the sender Pylon.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OfferKind {
+    /// A single file, to be accepted with [`Pylon::accept_transfer`] or [`Pylon::accept_transfer_into`].
+    File,
+    /// A folder or a batch of files, to be accepted with [`Pylon::accept_folder_transfer`].
+    Folder,
+}
+
+/// A file or folder offered by the sender Pylon, awaiting acceptance or rejection.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingOffer {
+    /// The name of the offered file or folder.
+    ///
+    /// **Security warning:** this is chosen by the sender and must not be trusted as a destination path.
+    pub name: String,
+    /// The number of bytes to transfer. For folders, this is the size of the archive they are sent as.
+    pub size: u64,
+    /// What is offered.
+    pub kind: OfferKind,
+}
+
+/// What to do when the destination of a received file or folder already exists.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CollisionPolicy {
+    /// Replace the existing file. Received folders are merged into the existing folder.
+    #[default]
+    Overwrite,
+    /// Fail with [`PylonError::DestinationExists`], leaving the transfer request active.
+    Fail,
+    /// Receive into the first free path of the form `name (1).ext` instead.
+    Rename,
+    /// Reject the transfer, leaving the existing file untouched.
+    Skip,
+}
+
+/// A relay server to offer for transit connections, in addition to the one at the Pylon's relay URL.
+///
+/// Relay servers are added with [`PylonBuilder::relays`] or [`PylonBuilder::relay`], and are offered in that order,
+/// after the one at the relay URL. Note that the underlying transit implementation only tries the first two relay
+/// servers offered by each side, i.e. the one at the relay URL and the first one added.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Relay {
+    /// The name of the relay server, as reported by [`TransitInfo::Relay`] if the transfer goes through it.
+    pub name: Option<String>,
+    /// The URLs of the relay server, e.g. `tcp://relay.example.com:4001`. They must all point to the same server.
+    pub urls: Vec<String>,
+}
+
+/// How failed connection attempts are retried, see [`PylonBuilder::retry_policy`].
+///
+/// The delay before each retry doubles, starting from `initial_delay` and capped at `max_delay`. With jitter, each
+/// delay is shortened by a random amount of up to half its length, so that many Pylons don't retry in lockstep.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one. A value of 1 disables retrying.
+    pub max_attempts: u32,
+    /// The delay before the first retry.
+    #[serde(rename = "initialDelayMs", serialize_with = "serialize_millis")]
+    pub initial_delay: Duration,
+    /// The maximum delay before a retry.
+    #[serde(rename = "maxDelayMs", serialize_with = "serialize_millis")]
+    pub max_delay: Duration,
+    /// Whether to randomize the delays.
+    pub jitter: bool,
+    /// The codes of the errors to retry, as returned by [`PylonError::code`]. If `None`, the errors for which
+    /// [`PylonError::is_retryable`] holds are retried.
+    pub retry_on: Option<Vec<String>>,
+}
+
+impl RetryPolicy {
+    /// Returns a policy that never retries.
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Returns whether an attempt that failed with the given error should be retried.
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The error the attempt failed with.
+    fn retries(&self, error: &PylonError) -> bool {
+        match &self.retry_on {
+            Some(codes) => codes.iter().any(|code| code == error.code()),
+            None => error.is_retryable(),
+        }
+    }
+
+    /// Returns the delay before the given retry.
+    ///
+    /// # Arguments
+    ///
+    /// * `retry` - The number of the retry, starting from 1.
+    fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let delay = self
+            .initial_delay
+            .saturating_mul(factor)
+            .min(self.max_delay);
+        match self.jitter {
+            true => delay.mul_f64(1.0 - rand::random::<f64>() / 2.0),
+            false => delay,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+            retry_on: None,
+        }
+    }
+}
+
+/// Limits on how long each phase of a transfer may take, see [`PylonBuilder::timeouts`].
+///
+/// A phase that takes longer fails with [`PylonError::Timeout`]. `None` means that the phase may take any time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Timeouts {
+    /// The limit on connecting to the rendezvous server. For the receiver Pylon, this includes the key exchange with
+    /// the sender Pylon. Applies to each attempt.
+    #[serde(rename = "rendezvousMs", serialize_with = "serialize_optional_millis")]
+    pub rendezvous: Option<Duration>,
+    /// The limit on waiting for the receiver Pylon to join the wormhole, or for the sender Pylon to make its offer.
+    #[serde(rename = "peerWaitMs", serialize_with = "serialize_optional_millis")]
+    pub peer_wait: Option<Duration>,
+    /// The limit on setting up and negotiating the transit connection.
+    #[serde(rename = "transitMs", serialize_with = "serialize_optional_millis")]
+    pub transit: Option<Duration>,
+    /// The limit on how long the transfer of the payload may go without any progress.
+    #[serde(rename = "idleMs", serialize_with = "serialize_optional_millis")]
+    pub idle: Option<Duration>,
+}
+
+/// A phase of a transfer, as reported by [`PylonEvent::Retrying`] and [`PylonError::Timeout`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TransferPhase {
+    /// Connecting to the rendezvous server.
+    Rendezvous,
+    /// Waiting for the peer Pylon to join the wormhole or to make its offer.
+    PeerWait,
+    /// Setting up the transit connection to the peer Pylon.
+    Transit,
+    /// Transferring the payload.
+    Transfer,
+}
+
+/// The stage of its lifecycle a Pylon is in, as returned by [`Pylon::state`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PylonState {
+    /// Nothing is in progress. A code can be generated, or a transfer requested.
+    #[default]
+    Idle,
+    /// A code was generated or claimed and the Pylon is waiting for the receiver Pylon to join the wormhole.
+    CodeGenerated,
+    /// The peer Pylon joined the wormhole.
+    Connected,
+    /// The sender Pylon offered a file or folder, which can be accepted or rejected.
+    OfferPending,
+    /// A payload is being transferred.
+    Transferring,
+    /// The last transfer completed successfully. The Pylon can start over.
+    Done,
+    /// The last operation failed. The Pylon can start over.
+    Failed,
+}
+
+/// An event in the lifecycle of a Pylon, as yielded by the stream returned by [`Pylon::events`].
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum PylonEvent {
+    /// A wormhole code was generated or claimed, to be passed on to the receiver Pylon.
+    CodeAllocated { code: String },
+    /// The peer Pylon joined the wormhole.
+    PeerConnected,
+    /// The key exchange with the peer Pylon yielded a verifier, to be compared with the one of the peer Pylon. If the
+    /// Pylon has a [`PylonConfirmToken`], it waits for the verifier to be confirmed before going any further.
+    VerifierReady { verifier: Verifier },
+    /// A transit connection to the peer Pylon was established.
+    TransitEstablished {
+        #[serde(serialize_with = "serialize_transit_info")]
+        info: TransitInfo,
+        addr: SocketAddr,
+    },
+    /// Part of the payload was transferred.
+    Progress { bytes: u64, total: u64 },
+    /// The transfer completed successfully.
+    Completed,
+    /// The transfer failed.
+    Failed { error: ErrorPayload },
+    /// The transfer was cancelled through its [`PylonCancelToken`].
+    Cancelled,
+    /// The transfer was skipped because of the Pylon's [`CollisionPolicy`].
+    Skipped,
+    /// An attempt failed and will be retried after a delay, according to the Pylon's [`RetryPolicy`].
+    Retrying {
+        phase: TransferPhase,
+        /// The number of the retry, starting from 1.
+        attempt: u32,
+        #[serde(rename = "delayMs", serialize_with = "serialize_millis")]
+        delay: Duration,
+        error: ErrorPayload,
+    },
+}
+
+/// Serializes a duration as a whole number of milliseconds.
+///
+/// # Arguments
+///
+/// * `duration` - The duration.
+/// * `serializer` - The serializer to use.
+fn serialize_millis<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_u128(duration.as_millis())
+}
+
+/// Serializes an optional duration as a whole number of milliseconds.
+///
+/// # Arguments
+///
+/// * `duration` - The duration, if any.
+/// * `serializer` - The serializer to use.
+fn serialize_optional_millis<S>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match duration {
+        Some(duration) => serialize_millis(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Serializes transit information, e.g. of a [`PylonEvent::TransitEstablished`] event.
+///
+/// # Arguments
+///
+/// * `info` - The transit information.
+/// * `serializer` - The serializer to use.
+fn serialize_transit_info<S>(info: &TransitInfo, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    #[derive(Serialize)]
+    #[serde(tag = "kind", rename_all = "camelCase")]
+    enum Info<'a> {
+        Direct,
+        Relay { name: Option<&'a str> },
+        Unknown,
+    }
+
+    match info {
+        TransitInfo::Direct => Info::Direct,
+        TransitInfo::Relay { name } => Info::Relay {
+            name: name.as_deref(),
+        },
+        _ => Info::Unknown,
+    }
+    .serialize(serializer)
+}
+
+/// A token to request cancellation of a transfer, e.g. from a UI.
+///
+/// Clones of a token share its state, and can be sent to other threads. Once a token is cancelled, any transfer it
+/// was passed to stops and fails with [`PylonError::Cancelled`], as does any transfer it is passed to later on.
+#[derive(Clone, Debug)]
+pub struct PylonCancelToken {
+    // Nothing is ever sent over the channel: closing it is what signals cancellation to all clones at once.
+    sender: Sender<()>,
+    receiver: Receiver<()>,
+}
+
+impl PylonCancelToken {
+    /// Returns a new token that is not cancelled.
+    pub fn new() -> Self {
+        let (sender, receiver) = smol::channel::bounded(1);
+        Self { sender, receiver }
+    }
+
+    /// Requests cancellation of the transfers this token was passed to.
+    pub fn cancel(&self) {
+        self.sender.close();
+    }
+
+    /// Returns whether cancellation was requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    /// Resolves once cancellation is requested.
+    pub async fn cancelled(&self) {
+        let _ = self.receiver.recv().await;
+    }
+
+    /// Runs the future to completion, failing with [`PylonError::Cancelled`] if cancellation is requested first.
+    ///
+    /// # Arguments
+    ///
+    /// * `run` - The future to run.
+    async fn guard<T>(&self, run: impl Future<Output = T>) -> Result<T, PylonError> {
+        smol::future::or(async { Ok(run.await) }, async {
+            self.cancelled().await;
+            Err(PylonError::Cancelled)
+        })
+        .await
+    }
+}
+
+impl Default for PylonCancelToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A token to confirm or reject the verifier of a wormhole, e.g. from a UI, see [`PylonBuilder::confirm_token`].
+///
+/// A Pylon with a token waits after the key exchange until the verifier is confirmed or rejected through it, so that
+/// nothing is transferred before the users compared the verifiers of both Pylons. Clones of a token share its state,
+/// and can be sent to other threads.
+#[derive(Clone, Debug)]
+pub struct PylonConfirmToken {
+    /// The verifier awaiting confirmation, if any.
+    pending: Arc<Mutex<Option<Verifier>>>,
+    sender: Sender<bool>,
+    receiver: Receiver<bool>,
+}
+
+impl PylonConfirmToken {
+    /// Returns a new token.
+    pub fn new() -> Self {
+        let (sender, receiver) = smol::channel::bounded(1);
+        Self {
+            pending: Arc::default(),
+            sender,
+            receiver,
+        }
+    }
+
+    /// Returns the verifier awaiting confirmation, if any. It is also announced with a [`PylonEvent::VerifierReady`]
+    /// event.
+    pub fn verifier(&self) -> Option<Verifier> {
+        self.pending.lock().unwrap().clone()
+    }
+
+    /// Confirms that the verifier awaiting confirmation matches the one of the peer Pylon, letting the transfer go on.
+    ///
+    /// This has no effect if no verifier is awaiting confirmation, so a verifier can't be confirmed before it is known.
+    pub fn confirm(&self) {
+        self.decide(true);
+    }
+
+    /// Rejects the verifier awaiting confirmation, which fails the transfer with [`PylonError::VerifierRejected`] on
+    /// both sides.
+    ///
+    /// This has no effect if no verifier is awaiting confirmation.
+    pub fn reject(&self) {
+        self.decide(false);
+    }
+
+    /// Confirms or rejects the verifier awaiting confirmation, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `confirmed` - Whether the verifier is confirmed.
+    fn decide(&self, confirmed: bool) {
+        let pending = self.pending.lock().unwrap();
+        if pending.is_some() {
+            // Only the first decision counts.
+            let _ = self.sender.try_send(confirmed);
+        }
+    }
+
+    /// Makes the given verifier the one awaiting confirmation, so that it can be confirmed or rejected from then on.
+    ///
+    /// # Arguments
+    ///
+    /// * `verifier` - The verifier to await confirmation of.
+    fn expect(&self, verifier: Verifier) {
+        *self.pending.lock().unwrap() = Some(verifier);
+    }
+
+    /// Waits until the verifier awaiting confirmation is confirmed or rejected, and returns whether it was confirmed.
+    async fn decision(&self) -> bool {
+        self.receiver.recv().await.unwrap_or(false)
+    }
+
+    /// Clears the verifier awaiting confirmation, along with any decision on it that wasn't awaited.
+    fn clear(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        *pending = None;
+        while self.receiver.try_recv().is_ok() {}
+    }
+}
+
+impl Default for PylonConfirmToken {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -94,16 +880,46 @@ pub struct Pylon {
     id: String,
     #[builder(default = "DEFAULT_RELAY_SERVER.into()")]
     relay_url: String,
+    #[builder(default, setter(each(name = "relay")))]
+    relays: Vec<Relay>,
     #[builder(default = "DEFAULT_RENDEZVOUS_SERVER.into()")]
     rendezvous_url: String,
     #[builder(default = "Abilities::ALL_ABILITIES")]
     abilities: Abilities,
+    #[builder(default)]
+    collision_policy: CollisionPolicy,
+    #[builder(default)]
+    resumable: bool,
+    #[builder(default)]
+    retry_policy: RetryPolicy,
+    #[builder(default)]
+    timeouts: Timeouts,
+    #[serde(rename = "codeExpiryMs", serialize_with = "serialize_optional_millis")]
+    #[builder(default)]
+    code_expiry: Option<Duration>,
+    #[serde(skip)]
+    #[builder(default = "Arc::new(PgpWordlist)")]
+    wordlist: Arc<dyn Wordlist>,
+    #[serde(skip)]
+    #[builder(default)]
+    confirm_token: Option<PylonConfirmToken>,
+    #[builder(setter(skip))]
+    state: PylonState,
+    #[serde(skip)]
+    #[builder(setter(skip))]
+    mailbox: Option<mailbox::Mailbox>,
+    #[serde(skip)]
+    #[builder(setter(skip))]
+    verifier: Option<Verifier>,
+    #[serde(skip)]
+    #[builder(setter(skip))]
+    peer_capabilities: Option<Capabilities>,
     #[serde(skip)]
     #[builder(setter(skip))]
-    handshake: Option<Box<Handshake>>,
+    transfer_request: Option<protocol::ReceiveRequest>,
     #[serde(skip)]
     #[builder(setter(skip))]
-    transfer_request: Option<ReceiveRequest>,
+    events: Option<Sender<PylonEvent>>,
 }
 
 // TODO: find a way to refactor this redundant signature.
@@ -141,203 +957,1754 @@ where
 }
 
 // TODO: find a way to refactor this redundant signature.
-/// Returns a default cancel handler if the specified handler is `None`.
+/// Returns a default per-file progress handler if the specified handler is `None`.
 ///
 /// # Arguments
 ///
-/// * `handler` - The cancel handler.
-fn get_cancel_handler<C>(mut handler: Option<C>) -> Pin<Box<dyn Future<Output = ()>>>
+/// * `handler` - The per-file progress handler.
+fn get_file_progress_handler<B>(mut handler: Option<B>) -> Box<dyn FnMut(usize, u64, u64) + 'static>
 where
-    C: Future<Output = ()> + 'static,
+    B: FnMut(usize, u64, u64) + 'static,
 {
     match handler.take() {
-        Some(c) => Box::pin(c),
-        None => Box::pin(async { loop {} }),
+        Some(b) => Box::new(b),
+        None => Box::new(|_: usize, _: u64, _: u64| {}),
+    }
+}
+
+/// Emits an event to the given event stream, if any.
+///
+/// Events are dropped if the stream was dropped.
+///
+/// # Arguments
+///
+/// * `events` - The sending end of the event stream.
+/// * `event` - The event to emit.
+fn emit(events: &Option<Sender<PylonEvent>>, event: PylonEvent) {
+    if let Some(events) = events {
+        let _ = events.try_send(event);
+    }
+}
+
+/// Runs an operation until it succeeds, fails with an error that is not retried, or runs out of attempts.
+///
+/// Each retry is announced with a [`PylonEvent::Retrying`] event.
+///
+/// # Arguments
+///
+/// * `policy` - The retry policy.
+/// * `phase` - The phase of the transfer the operation is part of.
+/// * `events` - The sending end of the event stream.
+/// * `operation` - Function that starts an attempt of the operation.
+async fn retry<T, F, O>(
+    policy: &RetryPolicy,
+    phase: TransferPhase,
+    events: &Option<Sender<PylonEvent>>,
+    mut operation: F,
+) -> Result<T, PylonError>
+where
+    F: FnMut() -> O,
+    O: Future<Output = Result<T, PylonError>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(error) if attempt < policy.max_attempts && policy.retries(&error) => {
+                let delay = policy.delay(attempt);
+                emit(
+                    events,
+                    PylonEvent::Retrying {
+                        phase,
+                        attempt,
+                        delay,
+                        error: (&error).into(),
+                    },
+                );
+                Timer::after(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Runs the future to completion, failing with [`PylonError::Timeout`] if it takes longer than the given duration.
+///
+/// # Arguments
+///
+/// * `duration` - The maximum duration of the future, if any.
+/// * `phase` - The phase of the transfer the future is part of.
+/// * `run` - The future to run.
+async fn timeout<T>(
+    duration: Option<Duration>,
+    phase: TransferPhase,
+    run: impl Future<Output = T>,
+) -> Result<T, PylonError> {
+    // The futures of connection phases are large, so they are kept on the heap rather than moved around the stack.
+    protocol::timeout(duration, Box::pin(run))
+        .await
+        .ok_or(PylonError::Timeout { phase })
+}
+
+/// Returns the archive entry for a file that is part of a batch transfer.
+///
+/// # Arguments
+///
+/// * `path` - The path of the file.
+async fn batch_entry(path: &Path) -> Result<archive::ArchiveEntry, PylonError> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| PylonError::InvalidPath(path.to_string_lossy().into()))?;
+    let metadata = smol::fs::metadata(path).await?;
+    if !metadata.is_file() {
+        return Err(PylonError::NotAFile(path.to_string_lossy().into()));
     }
+
+    Ok(archive::ArchiveEntry {
+        path: path.to_path_buf(),
+        name: name.into(),
+        is_dir: false,
+        size: metadata.len(),
+        offset: 0,
+    })
+}
+
+/// Writes an archive into the given pipe, closing it once done.
+///
+/// # Arguments
+///
+/// * `entries` - The entries of the archive.
+/// * `writer` - The writing end of the pipe.
+async fn write_archive(
+    entries: &[archive::ArchiveEntry],
+    mut writer: Writer,
+) -> std::io::Result<()> {
+    let result = archive::write(entries, &mut writer).await;
+    // Dropping the writer signals the end of the archive to the sender.
+    drop(writer);
+    result
 }
 
 impl Pylon {
     /// Builds and returns a wormhole app config.
-    fn config(&self) -> AppConfig<AppVersion> {
+    fn config(&self) -> AppConfig<protocol::AppVersion> {
         AppConfig {
             id: AppID(Cow::from(self.id.clone())),
             rendezvous_url: Cow::from(self.rendezvous_url.clone()),
-            app_version: AppVersion {},
+            app_version: protocol::AppVersion::ours(),
         }
     }
 
-    // TODO: add example(s)
-    /// Returns a generated wormhole code and connects to the rendezvous server.
+    /// Returns the relay hints to offer for transit connections: the one at the relay URL, followed by the configured
+    /// relay servers.
+    fn relay_hints(&self) -> Result<Vec<RelayHint>, PylonError> {
+        // Only the first two hints are tried by the peer, so the relay URL comes first to always be among them.
+        let mut hints = vec![RelayHint::from_urls(None, [self.relay_url.parse()?])?];
+        for relay in &self.relays {
+            if relay.urls.is_empty() {
+                let name = relay.name.as_deref().unwrap_or("(unnamed)");
+                return Err(PylonError::InvalidRelay(name.into()));
+            }
+            let urls = relay
+                .urls
+                .iter()
+                .map(|url| url.parse())
+                .collect::<Result<Vec<Url>, _>>()?;
+            hints.push(RelayHint::from_urls(relay.name.clone(), urls)?);
+        }
+
+        Ok(hints)
+    }
+
+    /// Returns the error for an operation that requires the Pylon to be in the expected state.
     ///
     /// # Arguments
     ///
-    /// * `code_length` - The required length of the wormhole code.
-    pub async fn gen_code(&mut self, code_length: usize) -> Result<String, PylonError> {
-        if let Some(_) = &self.handshake {
-            return Err(PylonError::CodegenError(
-                "The current Pylon already has a pending handshake".into(),
-            ));
+    /// * `expected` - The state required by the operation.
+    fn state_error(&self, expected: PylonState) -> PylonError {
+        PylonError::InvalidState {
+            expected,
+            actual: self.state,
         }
-
-        let (welcome, handshake) =
-            Wormhole::connect_without_code(self.config(), code_length).await?;
-        self.handshake = Some(Box::new(Box::pin(handshake)));
-
-        Ok(welcome.code.0)
     }
 
-    // TODO: add example(s)
-    /// Starts a file transfer over the wormhole network to the receiver Pylon.
+    /// Returns an error if the Pylon is not in the expected state.
+    ///
+    /// A Pylon that is done with its last transfer, successfully or not, counts as [`PylonState::Idle`].
     ///
     /// # Arguments
     ///
-    /// * `file` - The path of the file to send.
-    /// * `progress_handler` - Callback function that accepts the number of bytes sent and the total number of bytes to send.
+    /// * `expected` - The state required by the operation.
+    fn check_state(&self, expected: PylonState) -> Option<PylonError> {
+        let state = match self.state {
+            PylonState::Done | PylonState::Failed => PylonState::Idle,
+            state => state,
+        };
+        match state == expected {
+            true => None,
+            false => Some(self.state_error(expected)),
+        }
+    }
+
+    /// Moves the Pylon to the state following the outcome of an operation, and emits the matching event.
+    ///
+    /// If the operation didn't get to consume the pending code or transfer request, the Pylon stays ready to retry
+    /// it.
+    ///
+    /// # Arguments
+    ///
+    /// * `outcome` - Whether the operation transferred anything (as opposed to skipping the transfer), or the error it
+    ///   failed with.
+    fn settle(&mut self, outcome: Result<bool, &PylonError>) {
+        self.state = match outcome {
+            _ if self.transfer_request.is_some() => PylonState::OfferPending,
+            _ if self.mailbox.is_some() => PylonState::CodeGenerated,
+            Ok(true) => PylonState::Done,
+            Ok(false) | Err(PylonError::Cancelled) => PylonState::Idle,
+            Err(_) => PylonState::Failed,
+        };
+        self.emit(match outcome {
+            Ok(true) => PylonEvent::Completed,
+            Ok(false) => PylonEvent::Skipped,
+            Err(PylonError::Cancelled) => PylonEvent::Cancelled,
+            Err(e) => PylonEvent::Failed { error: e.into() },
+        });
+    }
+
+    /// Emits an event to the stream returned by [`Pylon::events`], if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event to emit.
+    fn emit(&self, event: PylonEvent) {
+        emit(&self.events, event);
+    }
+
+    /// Returns a progress handler that also emits [`PylonEvent::Progress`] events.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The progress handler to wrap.
+    fn observe_progress(&self, mut handler: Box<dyn FnMut(u64, u64)>) -> Box<dyn FnMut(u64, u64)> {
+        let events = self.events.clone();
+        Box::new(move |bytes, total| {
+            handler(bytes, total);
+            emit(&events, PylonEvent::Progress { bytes, total });
+        })
+    }
+
+    /// Returns a transit handler that also emits [`PylonEvent::TransitEstablished`] events.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The transit handler to wrap.
+    fn observe_transit(
+        &self,
+        mut handler: Box<dyn FnMut(TransitInfo, SocketAddr)>,
+    ) -> Box<dyn FnMut(TransitInfo, SocketAddr)> {
+        let events = self.events.clone();
+        Box::new(move |info, addr| {
+            emit(
+                &events,
+                PylonEvent::TransitEstablished {
+                    info: info.clone(),
+                    addr,
+                },
+            );
+            handler(info, addr);
+        })
+    }
+
+    /// Opens a mailbox on the rendezvous server for the code to pass on to the receiver Pylon, retrying according to
+    /// the retry policy, and returns the code.
+    ///
+    /// If the previous code expired without being used, it is abandoned first.
+    ///
+    /// # Arguments
+    ///
+    /// * `open` - Function that starts an attempt to open the mailbox with the given wormhole app config.
+    async fn open_mailbox<F, O>(&mut self, open: F) -> Result<String, PylonError>
+    where
+        F: Fn(AppConfig<protocol::AppVersion>) -> O,
+        O: Future<Output = Result<mailbox::Mailbox, WormholeError>>,
+    {
+        if self
+            .mailbox
+            .as_ref()
+            .map_or(false, mailbox::Mailbox::expired)
+        {
+            // The rendezvous server releases the mailbox along with the connection anyway.
+            let _ = self.abandon_code().await;
+        }
+        if let Some(e) = self.check_state(PylonState::Idle) {
+            return Err(e);
+        }
+
+        let config = self.config();
+        let duration = self.timeouts.rendezvous;
+        let open = retry(
+            &self.retry_policy,
+            TransferPhase::Rendezvous,
+            &self.events,
+            || {
+                let open = open(config.clone());
+                async move { Ok(timeout(duration, TransferPhase::Rendezvous, open).await??) }
+            },
+        );
+        let mailbox = match open.await {
+            Ok(mailbox) => mailbox,
+            Err(e) => {
+                self.state = PylonState::Failed;
+                self.emit(PylonEvent::Failed { error: (&e).into() });
+                return Err(e);
+            }
+        };
+        let code = mailbox.code().to_string();
+        self.mailbox = Some(mailbox);
+        self.state = PylonState::CodeGenerated;
+        self.emit(PylonEvent::CodeAllocated { code: code.clone() });
+
+        Ok(code)
+    }
+
+    /// Waits for the peer Pylon to join the mailbox of the code, and returns the established wormhole.
+    ///
+    /// Fails with [`PylonError::CodeExpired`] if the code expires first.
+    ///
+    /// # Arguments
+    ///
+    /// * `cancel_token` - Token to stop waiting for the peer Pylon.
+    async fn connect(&mut self, cancel_token: &PylonCancelToken) -> Result<Wormhole, PylonError> {
+        let mailbox = match self.mailbox.take() {
+            None => return Err(self.state_error(PylonState::CodeGenerated)),
+            Some(mailbox) if mailbox.expired() => {
+                let _ = mailbox.close().await;
+                return Err(PylonError::CodeExpired);
+            }
+            Some(mailbox) => mailbox,
+        };
+        let lifetime = mailbox.lifetime();
+        let handshake = mailbox.handshake().ok_or(PylonError::CodeExpired)?;
+        let handshake = timeout(self.timeouts.peer_wait, TransferPhase::PeerWait, handshake);
+        let wait = protocol::timeout(lifetime, handshake);
+        let wh = cancel_token
+            .guard(wait)
+            .await?
+            .ok_or(PylonError::CodeExpired)???;
+        self.connected(&wh);
+
+        self.verify(wh, cancel_token).await
+    }
+
+    /// Moves the Pylon to [`PylonState::Connected`] once the peer Pylon joined the wormhole, and keeps track of its
+    /// capabilities.
+    ///
+    /// # Arguments
+    ///
+    /// * `wormhole` - The wormhole connected to the peer Pylon.
+    fn connected(&mut self, wormhole: &Wormhole) {
+        self.peer_capabilities = Some(protocol::peer_capabilities(wormhole));
+        self.state = PylonState::Connected;
+        self.emit(PylonEvent::PeerConnected);
+    }
+
+    /// Makes the verifier of the established wormhole known and, if the Pylon has a confirmation token, waits until it
+    /// is confirmed.
+    ///
+    /// If the verifier is rejected or the wait is cancelled, the wormhole is closed, letting the peer Pylon know.
+    ///
+    /// # Arguments
+    ///
+    /// * `wormhole` - The wormhole connected to the peer Pylon.
+    /// * `cancel_token` - Token to stop waiting for confirmation.
+    async fn verify(
+        &mut self,
+        wormhole: Wormhole,
+        cancel_token: &PylonCancelToken,
+    ) -> Result<Wormhole, PylonError> {
+        let verifier = Verifier::new(wormhole.verifier.as_slice());
+        self.verifier = Some(verifier.clone());
+        // The verifier awaits confirmation before it is announced, so that it can be confirmed in reaction to the event.
+        if let Some(confirm_token) = &self.confirm_token {
+            confirm_token.expect(verifier.clone());
+        }
+        self.emit(PylonEvent::VerifierReady { verifier });
+        let confirm_token = match &self.confirm_token {
+            None => return Ok(wormhole),
+            Some(confirm_token) => confirm_token,
+        };
+
+        let decision = cancel_token.guard(confirm_token.decision()).await;
+        confirm_token.clear();
+        match decision {
+            Ok(true) => Ok(wormhole),
+            Ok(false) => {
+                protocol::abort(wormhole, Some(protocol::VERIFIER_REJECTED.into())).await;
+                Err(PylonError::VerifierRejected)
+            }
+            Err(e) => {
+                protocol::abort(wormhole, None).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Sets up our side of the transit connection to the peer Pylon, retrying according to the retry policy.
+    ///
+    /// If this fails, the wormhole is closed, letting the peer Pylon know.
+    ///
+    /// # Arguments
+    ///
+    /// * `wormhole` - The wormhole connected to the peer Pylon.
+    /// * `relay_hints` - The relay servers to offer for the transit connection.
+    /// * `cancel_token` - Token to request cancellation of the transfer.
+    async fn init_transit(
+        &self,
+        wormhole: Wormhole,
+        relay_hints: Vec<RelayHint>,
+        cancel_token: &PylonCancelToken,
+    ) -> Result<(Wormhole, TransitConnector), PylonError> {
+        let abilities = self.abilities;
+        let duration = self.timeouts.transit;
+        let relay_hints = &relay_hints;
+        let init = retry(
+            &self.retry_policy,
+            TransferPhase::Transit,
+            &self.events,
+            || async move {
+                let init = transit::init(abilities, None, relay_hints.clone());
+                Ok(timeout(duration, TransferPhase::Transit, init).await??)
+            },
+        );
+
+        match cancel_token.guard(init).await {
+            Ok(Ok(connector)) => Ok((wormhole, connector)),
+            Ok(Err(e)) => {
+                protocol::abort(wormhole, Some(e.to_string())).await;
+                Err(e)
+            }
+            Err(e) => {
+                protocol::abort(wormhole, None).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Sends the data read from the given source to the receiver Pylon as a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source of the data to send, positioned at its start. It must yield exactly `size` bytes.
+    /// * `name` - The file name to offer the data under.
+    /// * `size` - The number of bytes to send.
+    /// * `progress_handler` - Callback function that accepts the number of bytes sent and the total number of bytes to send.
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+    /// * `cancel_token` - Token to request cancellation of the transfer.
+    async fn send_from<R>(
+        &mut self,
+        source: &mut R,
+        name: PathBuf,
+        size: u64,
+        progress_handler: Box<dyn FnMut(u64, u64)>,
+        transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)>,
+        cancel_token: &PylonCancelToken,
+    ) -> Result<TransferReport, PylonError>
+    where
+        R: AsyncRead + AsyncSeek + Unpin,
+    {
+        let relay_hints = self.relay_hints()?;
+
+        let wh = self.connect(cancel_token).await?;
+        let (wh, connector) = self.init_transit(wh, relay_hints, cancel_token).await?;
+        let state = &mut self.state;
+        let transferred = protocol::send(
+            wh,
+            connector,
+            self.timeouts,
+            protocol::Offer::file(name, size),
+            protocol::Source::Seekable(source),
+            size,
+            || *state = PylonState::Transferring,
+            transit_handler,
+            progress_handler,
+            cancel_token.cancelled(),
+        )
+        .await?
+        .ok_or(PylonError::Cancelled)?;
+
+        Ok(TransferReport::new(None, transferred))
+    }
+
+    /// Sends the file at the given path to the receiver Pylon.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The path of the file to send.
+    /// * `progress_handler` - Callback function that accepts the number of bytes sent and the total number of bytes to send.
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+    /// * `cancel_token` - Token to request cancellation of the file transfer.
+    async fn send_file(
+        &mut self,
+        file: &Path,
+        progress_handler: Box<dyn FnMut(u64, u64)>,
+        transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)>,
+        cancel_token: &PylonCancelToken,
+    ) -> Result<TransferReport, PylonError> {
+        let file_name = file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| PylonError::InvalidPath(file.to_string_lossy().into()))?
+            .to_owned();
+        let mut file = File::open(file).await?;
+        let file_size = file.metadata().await?.len();
+
+        self.send_from(
+            &mut file,
+            file_name.into(),
+            file_size,
+            progress_handler,
+            transit_handler,
+            cancel_token,
+        )
+        .await
+    }
+
+    /// Sends the given archive entries to the receiver Pylon as a folder.
+    ///
+    /// Returns what was transferred of the archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the folder.
+    /// * `entries` - The entries of the archive, as laid out by [`archive::layout`].
+    /// * `archive_size` - The size of the archive, as returned by [`archive::layout`].
+    /// * `progress_handler` - Callback function that accepts the number of bytes sent and the total number of bytes to send.
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+    /// * `cancel_token` - Token to request cancellation of the transfer.
+    async fn send_archive(
+        &mut self,
+        name: String,
+        entries: &[archive::ArchiveEntry],
+        archive_size: u64,
+        progress_handler: Box<dyn FnMut(u64, u64)>,
+        transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)>,
+        cancel_token: &PylonCancelToken,
+    ) -> Result<protocol::Transferred, PylonError> {
+        let relay_hints = self.relay_hints()?;
+        let files = entries.iter().filter(|e| !e.is_dir);
+        let offer = protocol::Offer::folder(
+            name,
+            archive_size,
+            files.clone().map(|e| e.size).sum(),
+            files.count() as u64,
+        );
+
+        let wh = self.connect(cancel_token).await?;
+        let (wh, connector) = self.init_transit(wh, relay_hints, cancel_token).await?;
+        let timeouts = self.timeouts;
+        let state = &mut self.state;
+
+        // The archive is written while it is being sent, so we pipe the archive into the sender.
+        let (mut reader, writer) = piper::pipe(FOLDER_PIPE_CAPACITY);
+        let send = async move {
+            let result = protocol::send(
+                wh,
+                connector,
+                timeouts,
+                offer,
+                protocol::Source::Stream(&mut reader),
+                archive_size,
+                || *state = PylonState::Transferring,
+                transit_handler,
+                progress_handler,
+                cancel_token.cancelled(),
+            )
+            .await;
+            // Dropping the reader stops the archive writer if the transfer ended early.
+            drop(reader);
+            result
+        };
+        let (sent, written) = smol::future::zip(send, write_archive(entries, writer)).await;
+        let transferred = sent?.ok_or(PylonError::Cancelled)?;
+        written?;
+
+        Ok(transferred)
+    }
+
+    /// Sends the folder at the given path to the receiver Pylon.
+    ///
+    /// # Arguments
+    ///
+    /// * `folder` - The path of the folder to send.
+    /// * `progress_handler` - Callback function that accepts the number of bytes sent and the total number of bytes to send.
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+    /// * `cancel_token` - Token to request cancellation of the folder transfer.
+    async fn send_folder(
+        &mut self,
+        folder: &Path,
+        progress_handler: Box<dyn FnMut(u64, u64)>,
+        transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)>,
+        cancel_token: &PylonCancelToken,
+    ) -> Result<TransferReport, PylonError> {
+        let folder_name = folder
+            .file_name()
+            .ok_or_else(|| PylonError::InvalidPath(folder.to_string_lossy().into()))?
+            .to_string_lossy()
+            .into_owned();
+        let is_dir = smol::fs::metadata(folder).await?.is_dir();
+        if !is_dir {
+            return Err(PylonError::NotAFolder(folder.to_string_lossy().into()));
+        }
+        let mut entries = archive::walk(folder).await?;
+        let archive_size = archive::layout(&mut entries)?;
+
+        let transferred = self
+            .send_archive(
+                folder_name,
+                &entries,
+                archive_size,
+                progress_handler,
+                transit_handler,
+                cancel_token,
+            )
+            .await?;
+
+        Ok(TransferReport::new(None, transferred))
+    }
+
+    /// Sends the files at the given paths to the receiver Pylon as a single archive.
+    ///
+    /// Returns the outcome for each file, along with the index of the file whose outcome is the error that the transfer
+    /// failed with, if it failed once sending had started.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The paths of the files to send.
+    /// * `progress_handler` - Callback function that accepts the number of bytes sent and the total number of bytes to send.
+    /// * `file_progress_handler` - Callback function that accepts the index of a file in `files`, the number of bytes of
+    ///   that file sent and its size.
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+    /// * `cancel_token` - Token to request cancellation of the batch transfer.
+    async fn send_batch(
+        &mut self,
+        files: Vec<PathBuf>,
+        mut progress_handler: Box<dyn FnMut(u64, u64)>,
+        mut file_progress_handler: Box<dyn FnMut(usize, u64, u64)>,
+        transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)>,
+        cancel_token: &PylonCancelToken,
+    ) -> Result<(Vec<BatchFileResult>, Option<usize>), PylonError> {
+        let mut results = Vec::new();
+        let mut entries: Vec<archive::ArchiveEntry> = Vec::new();
+        // Index into `results` of the file each archive entry was created from.
+        let mut indices = Vec::new();
+        for path in files {
+            let entry = match batch_entry(&path).await {
+                Ok(entry) if entries.iter().any(|e| e.name == entry.name) => Err(
+                    PylonError::DuplicateName(entry.name.to_string_lossy().into()),
+                ),
+                entry => entry,
+            };
+            let error = match entry {
+                Ok(entry) => {
+                    indices.push(results.len());
+                    entries.push(entry);
+                    None
+                }
+                Err(e) => Some(e),
+            };
+            results.push(BatchFileResult {
+                path,
+                bytes_sent: 0,
+                error,
+            });
+        }
+        if entries.is_empty() {
+            return Err(PylonError::EmptyBatch);
+        }
+        let archive_size = archive::layout(&mut entries)?;
+
+        // Translates the progress through the archive into the progress through each file.
+        let spans: Vec<(usize, u64, u64)> = entries
+            .iter()
+            .zip(&indices)
+            .map(|(e, &i)| (i, e.offset, e.size))
+            .collect();
+        let mut current = 0;
+        let archive_sent = Rc::new(Cell::new(0));
+        let progress = archive_sent.clone();
+        let batch_progress_handler = move |sent: u64, total: u64| {
+            progress.set(sent);
+            progress_handler(sent, total);
+            while let Some(&(index, offset, size)) = spans.get(current) {
+                if sent < offset + size {
+                    if sent > offset {
+                        file_progress_handler(index, sent - offset, size);
+                    }
+                    break;
+                }
+                file_progress_handler(index, size, size);
+                current += 1;
+            }
+        };
+
+        let sent = self
+            .send_archive(
+                BATCH_FOLDER_NAME.into(),
+                &entries,
+                archive_size,
+                Box::new(batch_progress_handler),
+                transit_handler,
+                cancel_token,
+            )
+            .await;
+        let mut error = match sent {
+            Ok(_) => None,
+            // Nothing was sent if the transfer failed before its first record, e.g. because the receiver Pylon
+            // rejected it.
+            Err(e) if archive_sent.get() == 0 => return Err(e),
+            Err(e) => Some(e),
+        };
+
+        let archive_sent = match error {
+            None => archive_size,
+            Some(_) => archive_sent.get(),
+        };
+        let mut failed = None;
+        for (i, (entry, &index)) in entries.iter().zip(&indices).enumerate() {
+            let result = &mut results[index];
+            result.bytes_sent = archive_sent.saturating_sub(entry.offset).min(entry.size);
+            // The error goes to the file that was being sent when the transfer failed. If all data was sent, the
+            // transfer failed while awaiting confirmation, which was never given for the last file.
+            let sending = result.bytes_sent < entry.size || i == entries.len() - 1;
+            if failed.is_some() {
+                result.error = Some(PylonError::BatchAborted);
+            } else if error.is_some() && sending {
+                result.error = error.take();
+                failed = Some(index);
+            }
+        }
+
+        Ok((results, failed))
+    }
+
+    /// Applies the collision policy to the destination of the active transfer request.
+    ///
+    /// Returns the path to receive into, or `None` if the transfer was skipped, in which case it has been rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `destination` - The requested destination path.
+    async fn resolve_destination(
+        &mut self,
+        destination: &Path,
+    ) -> Result<Option<PathBuf>, PylonError> {
+        if self.transfer_request.is_none() {
+            return Err(self.state_error(PylonState::OfferPending));
+        }
+        if !paths::exists(destination).await {
+            return Ok(Some(destination.to_path_buf()));
+        }
+
+        match self.collision_policy {
+            CollisionPolicy::Overwrite => Ok(Some(destination.to_path_buf())),
+            CollisionPolicy::Fail => Err(PylonError::DestinationExists(
+                destination.to_string_lossy().into(),
+            )),
+            CollisionPolicy::Rename => Ok(Some(paths::available(destination).await)),
+            CollisionPolicy::Skip => {
+                if let Some(r) = self.transfer_request.take() {
+                    r.reject().await?;
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Accepts the active transfer request and writes the received data into the given sink.
+    ///
+    /// Returns what was transferred.
+    ///
+    /// # Arguments
+    ///
+    /// * `sink` - The destination of the received data.
+    /// * `resume` - The part of the file that was received by an earlier transfer, if it is to be resumed.
+    /// * `progress_handler` - Callback function that accepts the number of bytes received and the total number of bytes
+    ///   to receive.
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+    /// * `cancel_token` - Token to request cancellation of the transfer.
+    async fn receive_into(
+        &mut self,
+        sink: protocol::Sink<'_>,
+        resume: Option<protocol::Resume>,
+        progress_handler: Box<dyn FnMut(u64, u64)>,
+        transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)>,
+        cancel_token: &PylonCancelToken,
+    ) -> Result<protocol::Transferred, PylonError> {
+        match self.transfer_request.take() {
+            None => Err(self.state_error(PylonState::OfferPending)),
+            Some(r) => {
+                self.state = PylonState::Transferring;
+                r.accept(
+                    transit_handler,
+                    progress_handler,
+                    sink,
+                    resume,
+                    self.timeouts,
+                    cancel_token.cancelled(),
+                )
+                .await?
+                .ok_or(PylonError::Cancelled)
+            }
+        }
+    }
+
+    /// Receives the active transfer request into the given file, through a `.part` file next to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The destination file path.
+    /// * `progress_handler` - Callback function that accepts the number of bytes received and the total number of bytes
+    ///   to receive.
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+    /// * `cancel_token` - Token to request cancellation of the file transfer.
+    async fn receive_file(
+        &mut self,
+        file: &Path,
+        progress_handler: Box<dyn FnMut(u64, u64)>,
+        transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)>,
+        cancel_token: &PylonCancelToken,
+    ) -> Result<Option<TransferReport>, PylonError> {
+        let offer = self.expect_offer(OfferKind::File)?;
+        // We don't want to leave an empty file behind if there is nothing to receive.
+        let file = match self.resolve_destination(file).await? {
+            None => return Ok(None),
+            Some(file) => file,
+        };
+        // The data is received into a temporary file next to the destination, which only replaces it once complete.
+        let mut part_name = file.file_name().unwrap_or_default().to_os_string();
+        part_name.push(PART_EXTENSION);
+        let part = file.with_file_name(part_name);
+        let resume = match self.resumable {
+            true => resume::load(&part, &offer).await,
+            false => None,
+        };
+        let mut sink = match &resume {
+            Some(resume) => {
+                let mut sink = smol::fs::OpenOptions::new().write(true).open(&part).await?;
+                sink.set_len(resume.offset).await?;
+                sink.seek(SeekFrom::Start(resume.offset)).await?;
+                sink
+            }
+            None => File::create(&part).await?,
+        };
+
+        let received = self
+            .receive_into(
+                protocol::Sink::Seekable(&mut sink),
+                resume,
+                progress_handler,
+                transit_handler,
+                cancel_token,
+            )
+            .await;
+        let synced = sink.sync_all().await;
+        drop(sink);
+
+        match received {
+            Ok(transferred) => {
+                synced?;
+                smol::fs::rename(&part, &file).await?;
+                resume::discard(&part).await;
+                Ok(Some(TransferReport::new(Some(file), transferred)))
+            }
+            // What was received of a failed transfer is kept, to continue from it next time.
+            Err(e)
+                if self.resumable
+                    && !matches!(e, PylonError::Cancelled)
+                    && synced.is_ok()
+                    && resume::save(&part, &offer).await.is_ok() =>
+            {
+                Err(e)
+            }
+            // Otherwise, nothing is kept of a failed or cancelled transfer.
+            Err(e) => {
+                let _ = smol::fs::remove_file(&part).await;
+                resume::discard(&part).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns the pending offer, failing with [`PylonError::UnexpectedOffer`] if it isn't of the given kind.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of offer the operation handles.
+    fn expect_offer(&self, kind: OfferKind) -> Result<PendingOffer, PylonError> {
+        match self.pending_offer() {
+            None => Err(self.state_error(PylonState::OfferPending)),
+            Some(offer) if offer.kind != kind => Err(PylonError::UnexpectedOffer {
+                expected: kind,
+                actual: offer.kind,
+            }),
+            Some(offer) => Ok(offer),
+        }
+    }
+
+    /// Receives the active folder transfer request, unpacking it into the given folder.
+    ///
+    /// # Arguments
+    ///
+    /// * `folder` - The destination folder path. It is created if it doesn't exist.
+    /// * `progress_handler` - Callback function that accepts the number of bytes received and the total number of bytes
+    ///   to receive.
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+    /// * `cancel_token` - Token to request cancellation of the folder transfer.
+    async fn receive_folder(
+        &mut self,
+        folder: &Path,
+        progress_handler: Box<dyn FnMut(u64, u64)>,
+        transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)>,
+        cancel_token: &PylonCancelToken,
+    ) -> Result<Option<TransferReport>, PylonError> {
+        self.expect_offer(OfferKind::Folder)?;
+        let folder = match self.resolve_destination(folder).await? {
+            None => return Ok(None),
+            Some(folder) => folder,
+        };
+        smol::fs::create_dir_all(&folder).await?;
+
+        // The archive is unpacked while it is being received, so we pipe the received bytes into the unpacker.
+        let (reader, mut writer) = piper::pipe(FOLDER_PIPE_CAPACITY);
+        let receive = async move {
+            let result = self
+                .receive_into(
+                    protocol::Sink::Stream(&mut writer),
+                    None,
+                    progress_handler,
+                    transit_handler,
+                    cancel_token,
+                )
+                .await;
+            // Dropping the writer signals the end of the archive to the unpacker.
+            drop(writer);
+            result
+        };
+        let unpack = async {
+            let mut entries = async_tar::Archive::new(reader).entries()?;
+            while let Some(entry) = entries.next().await {
+                let mut entry = entry?;
+                let path = entry.path()?.into_owned();
+                // Links could point outside of the destination folder, and are never sent by a Pylon anyway.
+                let entry_type = entry.header().entry_type();
+                if !paths::is_confined(path.as_ref())
+                    || !(entry_type.is_file() || entry_type.is_dir())
+                {
+                    return Err(PylonError::PathTraversal(path.to_string_lossy().into()));
+                }
+                entry.unpack_in(&folder).await?;
+            }
+            Ok(())
+        };
+        let (received, unpacked) = smol::future::zip(receive, unpack).await;
+        // A refused entry stops the unpacker, which breaks the pipe, so it takes precedence over the receive error.
+        if let Err(e @ PylonError::PathTraversal(_)) = unpacked {
+            return Err(e);
+        }
+        let transferred = received?;
+        unpacked?;
+
+        Ok(Some(TransferReport::new(Some(folder), transferred)))
+    }
+
+    /// Receives the active transfer request into the given download folder, under its sanitized name.
+    ///
+    /// # Arguments
+    ///
+    /// * `download_dir` - The folder to receive into. It is created if it doesn't exist.
+    /// * `progress_handler` - Callback function that accepts the number of bytes received and the total number of bytes
+    ///   to receive.
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+    /// * `cancel_token` - Token to request cancellation of the transfer.
+    async fn receive_to_dir(
+        &mut self,
+        download_dir: &Path,
+        progress_handler: Box<dyn FnMut(u64, u64)>,
+        transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)>,
+        cancel_token: &PylonCancelToken,
+    ) -> Result<Option<TransferReport>, PylonError> {
+        let offer = self
+            .pending_offer()
+            .ok_or_else(|| self.state_error(PylonState::OfferPending))?;
+        let destination = paths::resolve(download_dir, &offer.name)
+            .ok_or_else(|| PylonError::PathTraversal(offer.name.into()))?;
+
+        match offer.kind {
+            OfferKind::File => {
+                smol::fs::create_dir_all(download_dir).await?;
+                self.receive_file(
+                    &destination,
+                    progress_handler,
+                    transit_handler,
+                    cancel_token,
+                )
+                .await
+            }
+            OfferKind::Folder => {
+                self.receive_folder(
+                    &destination,
+                    progress_handler,
+                    transit_handler,
+                    cancel_token,
+                )
+                .await
+            }
+        }
+    }
+
+    /// Returns the stage of its lifecycle the Pylon is in.
+    ///
+    /// Operations that are not allowed in this state fail with [`PylonError::InvalidState`].
+    pub fn state(&self) -> PylonState {
+        self.state
+    }
+
+    /// Returns the capabilities that this Pylon advertises to the peer Pylon.
+    pub fn capabilities(&self) -> Capabilities {
+        protocol::AppVersion::ours().capabilities()
+    }
+
+    /// Returns the capabilities of the peer Pylon of the latest wormhole, if any.
+    ///
+    /// They are known once the peer Pylon joined the wormhole, e.g. as announced by [`PylonEvent::PeerConnected`].
+    pub fn peer_capabilities(&self) -> Option<&Capabilities> {
+        self.peer_capabilities.as_ref()
+    }
+
+    /// Returns the verifier of the latest wormhole established with a peer Pylon, if any.
+    ///
+    /// Comparing it with the verifier of the peer Pylon, e.g. by reading the emoji aloud, rules out that someone guessed
+    /// the code and got in between the Pylons. To do so before anything is transferred, see
+    /// [`PylonBuilder::confirm_token`].
+    pub fn verifier(&self) -> Option<&Verifier> {
+        self.verifier.as_ref()
+    }
+
+    // TODO: add example(s)
+    /// Returns a stream of the events of this Pylon, as an alternative to passing callback functions to each method.
+    ///
+    /// Events are emitted alongside the callback functions, so both can be used at once. Only the stream returned by
+    /// the latest call receives events, and events emitted before that call are not replayed. The stream must be polled
+    /// concurrently with the transfer, e.g. from another task.
+    pub fn events(&mut self) -> impl Stream<Item = PylonEvent> + Unpin {
+        let (sender, receiver) = smol::channel::unbounded();
+        self.events = Some(sender);
+        receiver
+    }
+
+    // TODO: add example(s)
+    /// Returns a generated wormhole code and connects to the rendezvous server.
+    ///
+    /// The words of the code are taken from the Pylon's wordlist. If the previously generated code expired without
+    /// being used, it is abandoned first.
+    ///
+    /// # Arguments
+    ///
+    /// * `code_length` - The required length of the wormhole code.
+    pub async fn gen_code(&mut self, code_length: usize) -> Result<String, PylonError> {
+        let password = wordlist::choose_words(&*self.wordlist, code_length)?;
+        let lifetime = self.code_expiry;
+        self.open_mailbox(|config| mailbox::Mailbox::allocate(config, password.clone(), lifetime))
+            .await
+    }
+
+    // TODO: add example(s)
+    /// Uses a wormhole code agreed on with the receiver Pylon beforehand instead of a generated one, and connects to the
+    /// rendezvous server.
+    ///
+    /// This allows configuring both Pylons ahead of time. The code must consist of a numeric nameplate and a password
+    /// separated by a dash, e.g. `42-orca-trumpet-7fq`, and its password must have an estimated entropy of at least
+    /// [`consts::MIN_CODE_ENTROPY`] bits. Otherwise, this fails with [`PylonError::InvalidCode`] or
+    /// [`PylonError::WeakCode`] respectively. Its words are normalized against the Pylon's wordlist, as they are by
+    /// the receiver Pylon.
+    ///
+    /// If the previous code expired without being used, it is abandoned first.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The wormhole code.
+    pub async fn use_code(&mut self, code: String) -> Result<(), PylonError> {
+        let code = code::normalize(&code, &*self.wordlist);
+        code::validate_agreed(&code, &*self.wordlist)?;
+
+        let lifetime = self.code_expiry;
+        self.open_mailbox(|config| mailbox::Mailbox::claim(config, code.clone(), lifetime))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns the `wormhole-transfer:` URI of a code, along with the settings of this Pylon that the receiver Pylon
+    /// needs, which can be rendered as a QR code.
+    ///
+    /// Only the relay URL is included, not any other relay servers.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The wormhole code, e.g. as returned by [`Pylon::gen_code`].
+    pub fn code_uri(&self, code: &str) -> uri::CodeUri {
+        uri::CodeUri::new(code, &self.rendezvous_url, &self.relay_url, &self.id)
+    }
+
+    /// Returns the nameplates that are currently in use on the rendezvous server, e.g. to complete a code with
+    /// [`code::complete`].
+    ///
+    /// Only available with the `nameplates` feature, which is enabled by default.
+    #[cfg(feature = "nameplates")]
+    pub async fn list_nameplates(&self) -> Result<Vec<String>, PylonError> {
+        let config = self.config();
+        let duration = self.timeouts.rendezvous;
+        let list = retry(
+            &self.retry_policy,
+            TransferPhase::Rendezvous,
+            &self.events,
+            || {
+                let list = nameplates::list(config.clone());
+                async move { Ok(timeout(duration, TransferPhase::Rendezvous, list).await??) }
+            },
+        );
+
+        list.await
+    }
+
+    /// Abandons the generated or claimed code before the receiver Pylon joined the wormhole.
+    ///
+    /// The nameplate of the code is released and its mailbox closed on the rendezvous server, so the code can no longer
+    /// be used. The Pylon goes back to [`PylonState::Idle`] even if this fails, and a fresh code can be generated.
+    pub async fn abandon_code(&mut self) -> Result<(), PylonError> {
+        if let Some(e) = self.check_state(PylonState::CodeGenerated) {
+            return Err(e);
+        }
+
+        let mailbox = self.mailbox.take();
+        self.state = PylonState::Idle;
+        match mailbox {
+            Some(mailbox) => Ok(mailbox.close().await?),
+            None => Ok(()),
+        }
+    }
+
+    // TODO: add example(s)
+    /// Starts a file transfer over the wormhole network to the receiver Pylon.
+    ///
+    /// Returns a report with the checksum of the sent file, failing with [`PylonError::Cancelled`] if the transfer was
+    /// cancelled. Fails with [`PylonError::IntegrityError`] if the receiver Pylon got different data.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The path of the file to send.
+    /// * `progress_handler` - Callback function that accepts the number of bytes sent and the total number of bytes to send.
     /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
-    /// * `cancel_handler` - Callback function to request cancellation of the file transfer.
-    pub async fn start_transfer<F, P, T, C>(
+    /// * `cancel_token` - Token to request cancellation of the file transfer.
+    pub async fn start_transfer<F, P, T>(
         &mut self,
         file: F,
         progress_handler: Option<P>,
         transit_handler: Option<T>,
-        cancel_handler: Option<C>,
-    ) -> Result<(), PylonError>
+        cancel_token: Option<PylonCancelToken>,
+    ) -> Result<TransferReport, PylonError>
     where
         F: AsRef<Path>,
         P: FnMut(u64, u64) + 'static,
         T: FnMut(TransitInfo, SocketAddr) + 'static,
-        C: Future<Output = ()> + 'static,
     {
-        let file_name = file
-            .as_ref()
-            .file_name()
-            .ok_or(PylonError::Error("could not extract file name".into()))?
-            .to_str()
-            .ok_or(PylonError::Error(
-                "could not convert file name to str".into(),
-            ))?;
-        let mut file = File::open(&file)
-            .await
-            .map_err(|e| PylonError::Error(e.into()))?;
-        let file_size = file
-            .metadata()
-            .await
-            .map_err(|e| PylonError::Error(e.into()))?
-            .len();
-        // TODO: allow caller to specify custom relay hints
-        let transit_abilities = self.abilities;
-        let relay_hints = vec![RelayHint::from_urls(None, [self.relay_url.parse()?])?];
+        if let Some(e) = self.check_state(PylonState::CodeGenerated) {
+            return Err(e);
+        }
 
         // We're providing fallback/default handlers if the caller hasn't provided them.
         let transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)> =
-            get_transit_handler(transit_handler);
-        let progress_handler: Box<dyn FnMut(u64, u64)> = get_progress_handler(progress_handler);
-        let cancel_handler: Pin<Box<dyn Future<Output = ()>>> = get_cancel_handler(cancel_handler);
-
-        let sender = match self.handshake.take() {
-            None => {
-                return Err(PylonError::Error(
-                    "There is currently no active handshake".into(),
-                ))
-            }
-            Some(h) => {
-                let wh = h.await?;
-                transfer::send_file(
-                    wh,
-                    relay_hints,
-                    &mut file,
-                    file_name,
-                    file_size,
-                    transit_abilities,
-                    transit_handler,
+            self.observe_transit(get_transit_handler(transit_handler));
+        let progress_handler: Box<dyn FnMut(u64, u64)> =
+            self.observe_progress(get_progress_handler(progress_handler));
+        let cancel_token = cancel_token.unwrap_or_default();
+
+        let result = self
+            .send_file(
+                file.as_ref(),
+                progress_handler,
+                transit_handler,
+                &cancel_token,
+            )
+            .await;
+        self.settle(result.as_ref().map(|_| true));
+
+        result
+    }
+
+    // TODO: add example(s)
+    /// Starts a transfer of arbitrary data over the wormhole network to the receiver Pylon.
+    ///
+    /// The data is read from the start of the source and offered to the receiver Pylon as a file with the given name
+    /// and size. This allows sending data that doesn't live on the filesystem, e.g. from a socket or an in-memory buffer.
+    ///
+    /// Returns a report with the checksum of the sent data, failing with [`PylonError::Cancelled`] if the transfer was
+    /// cancelled. Fails with [`PylonError::IntegrityError`] if the receiver Pylon got different data.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source of the data to send. It must yield exactly `size` bytes.
+    /// * `name` - The file name to offer the data under.
+    /// * `size` - The number of bytes to send.
+    /// * `progress_handler` - Callback function that accepts the number of bytes sent and the total number of bytes to send.
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+    /// * `cancel_token` - Token to request cancellation of the transfer.
+    pub async fn start_transfer_from<R, N, P, T>(
+        &mut self,
+        source: &mut R,
+        name: N,
+        size: u64,
+        progress_handler: Option<P>,
+        transit_handler: Option<T>,
+        cancel_token: Option<PylonCancelToken>,
+    ) -> Result<TransferReport, PylonError>
+    where
+        R: AsyncRead + AsyncSeek + Unpin,
+        N: Into<PathBuf>,
+        P: FnMut(u64, u64) + 'static,
+        T: FnMut(TransitInfo, SocketAddr) + 'static,
+    {
+        if let Some(e) = self.check_state(PylonState::CodeGenerated) {
+            return Err(e);
+        }
+
+        // We're providing fallback/default handlers if the caller hasn't provided them.
+        let transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)> =
+            self.observe_transit(get_transit_handler(transit_handler));
+        let progress_handler: Box<dyn FnMut(u64, u64)> =
+            self.observe_progress(get_progress_handler(progress_handler));
+        let cancel_token = cancel_token.unwrap_or_default();
+
+        let result = match source.seek(SeekFrom::Start(0)).await {
+            Ok(_) => {
+                self.send_from(
+                    source,
+                    name.into(),
+                    size,
                     progress_handler,
-                    cancel_handler,
+                    transit_handler,
+                    &cancel_token,
                 )
+                .await
             }
+            Err(e) => Err(e.into()),
         };
-        sender.await?;
+        self.settle(result.as_ref().map(|_| true));
 
-        Ok(())
+        result
     }
 
     // TODO: add example(s)
-    /// Requests for a file transfer from the sender Pylon.
+    /// Starts a folder transfer over the wormhole network to the receiver Pylon.
+    ///
+    /// The folder is streamed as a tar archive, with paths relative to the folder root. The receiver Pylon should
+    /// accept it with [`Pylon::accept_folder_transfer`] to recreate the folder tree.
+    ///
+    /// Returns a report with the checksum of the archive, failing with [`PylonError::Cancelled`] if the transfer was
+    /// cancelled.
+    ///
+    /// # Arguments
+    ///
+    /// * `folder` - The path of the folder to send.
+    /// * `progress_handler` - Callback function that accepts the number of bytes sent and the total number of bytes to
+    ///   send. The totals are those of the whole archive, i.e. the contents of all files plus tar headers.
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+    /// * `cancel_token` - Token to request cancellation of the folder transfer.
+    pub async fn start_folder_transfer<F, P, T>(
+        &mut self,
+        folder: F,
+        progress_handler: Option<P>,
+        transit_handler: Option<T>,
+        cancel_token: Option<PylonCancelToken>,
+    ) -> Result<TransferReport, PylonError>
+    where
+        F: AsRef<Path>,
+        P: FnMut(u64, u64) + 'static,
+        T: FnMut(TransitInfo, SocketAddr) + 'static,
+    {
+        if let Some(e) = self.check_state(PylonState::CodeGenerated) {
+            return Err(e);
+        }
+
+        // We're providing fallback/default handlers if the caller hasn't provided them.
+        let transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)> =
+            self.observe_transit(get_transit_handler(transit_handler));
+        let progress_handler: Box<dyn FnMut(u64, u64)> =
+            self.observe_progress(get_progress_handler(progress_handler));
+        let cancel_token = cancel_token.unwrap_or_default();
+
+        let result = self
+            .send_folder(
+                folder.as_ref(),
+                progress_handler,
+                transit_handler,
+                &cancel_token,
+            )
+            .await;
+        self.settle(result.as_ref().map(|_| true));
+
+        result
+    }
+
+    // TODO: add example(s)
+    /// Starts a transfer of multiple files over the wormhole network to the receiver Pylon, using a single wormhole
+    /// code.
+    ///
+    /// The files are streamed as a single tar archive, with each file at the root of the archive. The receiver Pylon
+    /// should accept it with [`Pylon::accept_folder_transfer`]. Files that cannot be read, or whose name clashes with
+    /// that of a previous file in the batch, are left out of the archive and reported as failed.
+    ///
+    /// Returns the outcome for each file. If the transfer fails or is cancelled once sending has started, the file that
+    /// was being sent is reported with the error (e.g. [`PylonError::Cancelled`]) and the number of its bytes that were
+    /// sent, and the files after it with [`PylonError::BatchAborted`], while the Pylon fails as it would for any other
+    /// transfer. The call itself only fails if none of the files can be sent, or if the transfer fails before sending
+    /// has started.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The paths of the files to send.
+    /// * `progress_handler` - Callback function that accepts the number of bytes sent and the total number of bytes to
+    ///   send. The totals are those of the whole archive, i.e. the contents of all files plus tar headers.
+    /// * `file_progress_handler` - Callback function that accepts the index of a file in `files`, the number of bytes of
+    ///   that file sent and its size.
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+    /// * `cancel_token` - Token to request cancellation of the batch transfer.
+    pub async fn start_batch_transfer<I, F, P, B, T>(
+        &mut self,
+        files: I,
+        progress_handler: Option<P>,
+        file_progress_handler: Option<B>,
+        transit_handler: Option<T>,
+        cancel_token: Option<PylonCancelToken>,
+    ) -> Result<Vec<BatchFileResult>, PylonError>
+    where
+        I: IntoIterator<Item = F>,
+        F: AsRef<Path>,
+        P: FnMut(u64, u64) + 'static,
+        B: FnMut(usize, u64, u64) + 'static,
+        T: FnMut(TransitInfo, SocketAddr) + 'static,
+    {
+        if let Some(e) = self.check_state(PylonState::CodeGenerated) {
+            return Err(e);
+        }
+
+        // We're providing fallback/default handlers if the caller hasn't provided them.
+        let transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)> =
+            self.observe_transit(get_transit_handler(transit_handler));
+        let progress_handler: Box<dyn FnMut(u64, u64)> =
+            self.observe_progress(get_progress_handler(progress_handler));
+        let file_progress_handler: Box<dyn FnMut(usize, u64, u64)> =
+            get_file_progress_handler(file_progress_handler);
+        let cancel_token = cancel_token.unwrap_or_default();
+
+        let files = files
+            .into_iter()
+            .map(|f| f.as_ref().to_path_buf())
+            .collect();
+        let result = self
+            .send_batch(
+                files,
+                progress_handler,
+                file_progress_handler,
+                transit_handler,
+                &cancel_token,
+            )
+            .await;
+        match &result {
+            Ok((results, Some(failed))) => self.settle(Err(results[*failed]
+                .error
+                .as_ref()
+                .expect("the failed file has an error"))),
+            Ok((_, None)) => self.settle(Ok(true)),
+            Err(e) => self.settle(Err(e)),
+        }
+
+        result.map(|(results, _)| results)
+    }
+
+    // TODO: add example(s)
+    /// Sends a text message over the wormhole network to the receiver Pylon.
+    ///
+    /// Text messages are small enough to be sent over the wormhole itself, so no transit connection is established.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The message to send.
+    /// * `cancel_token` - Token to request cancellation of the message transfer.
+    pub async fn send_text(
+        &mut self,
+        text: &str,
+        cancel_token: Option<PylonCancelToken>,
+    ) -> Result<(), PylonError> {
+        if let Some(e) = self.check_state(PylonState::CodeGenerated) {
+            return Err(e);
+        }
+
+        // We're providing a default cancel token if the caller hasn't provided one.
+        let cancel_token = cancel_token.unwrap_or_default();
+
+        let result = match self.connect(&cancel_token).await {
+            Ok(wh) => {
+                self.state = PylonState::Transferring;
+                match protocol::send_text(wh, text, cancel_token.cancelled()).await {
+                    Ok(sent) => sent.ok_or(PylonError::Cancelled),
+                    Err(e) => Err(e.into()),
+                }
+            }
+            Err(e) => Err(e),
+        };
+        self.settle(result.as_ref().map(|_| true));
+
+        result
+    }
+
+    // TODO: add example(s)
+    /// Requests for a transfer from the sender Pylon.
+    ///
+    /// If the sender Pylon offers a file or folder, it is kept as the active transfer request, which can be inspected
+    /// with [`Pylon::pending_offer`] before it is accepted or rejected. If it sends a text message instead, the
+    /// message is returned and there is nothing further to accept.
+    ///
+    /// The words of the code are normalized against the Pylon's wordlist, which must be the one the code was generated
+    /// with. Fails with [`PylonError::Cancelled`] if the request was cancelled.
     ///
     /// # Arguments
     ///
     /// * `code` - The wormhole code to authenticate the connection.
-    /// * `cancel_handler` - Callback function to request cancellation of the file transfer.
-    pub async fn request_transfer<C: Future<Output = ()> + 'static>(
+    /// * `cancel_token` - Token to request cancellation of the file transfer.
+    pub async fn request_transfer(
         &mut self,
         code: String,
-        cancel_handler: Option<C>,
-    ) -> Result<(), PylonError> {
-        // TODO: allow caller to specify transit abilities and relay hints
-        let transit_abilities = self.abilities;
-        let relay_hints = vec![RelayHint::from_urls(None, [self.relay_url.parse()?])?];
+        cancel_token: Option<PylonCancelToken>,
+    ) -> Result<Option<String>, PylonError> {
+        if let Some(e) = self.check_state(PylonState::Idle) {
+            return Err(e);
+        }
 
-        // We're providing a fallback/default cancel handler if the caller hasn't provided one.
-        let cancel_handler: Pin<Box<dyn Future<Output = ()>>> = get_cancel_handler(cancel_handler);
+        // TODO: allow caller to specify transit abilities
+        let relay_hints = self.relay_hints()?;
 
-        let (_, wh) = Wormhole::connect_with_code(self.config(), Code(code)).await?;
-        let request =
-            transfer::request_file(wh, relay_hints, transit_abilities, cancel_handler).await?;
-        self.transfer_request = request;
+        // We're providing a default cancel token if the caller hasn't provided one.
+        let cancel_token = cancel_token.unwrap_or_default();
 
-        Ok(())
+        let config = self.config();
+        let code = Code(code::normalize(&code, &*self.wordlist));
+        let duration = self.timeouts.rendezvous;
+        let connect = retry(
+            &self.retry_policy,
+            TransferPhase::Rendezvous,
+            &self.events,
+            || {
+                let connect = Wormhole::connect_with_code(config.clone(), code.clone());
+                async move { Ok(timeout(duration, TransferPhase::Rendezvous, connect).await??) }
+            },
+        );
+        let incoming = match cancel_token.guard(connect).await {
+            Ok(Ok((_, wh))) => {
+                self.connected(&wh);
+                match self.verify(wh, &cancel_token).await {
+                    Ok(wh) => match self.init_transit(wh, relay_hints, &cancel_token).await {
+                        Ok((wh, connector)) => {
+                            match protocol::request(
+                                wh,
+                                connector,
+                                self.timeouts,
+                                cancel_token.cancelled(),
+                            )
+                            .await
+                            {
+                                Ok(incoming) => incoming.ok_or(PylonError::Cancelled),
+                                Err(e) => Err(e.into()),
+                            }
+                        }
+                        Err(e) => Err(e),
+                    },
+                    Err(e) => Err(e),
+                }
+            }
+            Ok(Err(e)) | Err(e) => Err(e),
+        };
+        let text = match incoming {
+            Ok(protocol::Incoming::File(request)) => {
+                self.transfer_request = Some(*request);
+                self.state = PylonState::OfferPending;
+                return Ok(None);
+            }
+            Ok(protocol::Incoming::Text(text)) => Ok(Some(text)),
+            Err(e) => Err(e),
+        };
+        // A text message is complete as soon as it is received.
+        self.settle(text.as_ref().map(|_| true));
+
+        text
+    }
+
+    /// Returns the file or folder offered by the sender Pylon, if there is an active transfer request.
+    ///
+    /// This allows inspecting an offer before deciding whether to accept or reject it.
+    pub fn pending_offer(&self) -> Option<PendingOffer> {
+        self.transfer_request.as_ref().map(|r| r.offer.clone())
     }
 
     // TODO: add example(s)
-    /// Rejects a pending transfer request.
-    pub async fn reject_transfer(&mut self) -> Result<(), PylonError> {
-        if let Some(r) = self.transfer_request.take() {
-            r.reject().await?;
+    /// Rejects a pending transfer request, letting the sender Pylon know.
+    ///
+    /// Returns the offer that was rejected.
+    pub async fn reject_transfer(&mut self) -> Result<PendingOffer, PylonError> {
+        if let Some(e) = self.check_state(PylonState::OfferPending) {
+            return Err(e);
         }
+        let request = match self.transfer_request.take() {
+            None => return Err(self.state_error(PylonState::OfferPending)),
+            Some(request) => request,
+        };
 
-        return Err(PylonError::Error(
-            "There is currently no active transfer request".into(),
-        ));
+        let offer = request.offer.clone();
+        let rejected = request.reject().await;
+        self.state = match rejected {
+            Ok(_) => PylonState::Idle,
+            Err(_) => PylonState::Failed,
+        };
+        rejected?;
+
+        Ok(offer)
     }
 
     // TODO: add example(s)
     /// Accepts an active transfer and receives a file over the wormhole network from the sender Pylon.
     ///
+    /// The data is received into a `.part` file next to the destination, which atomically replaces the destination only
+    /// once the transfer has completed. If the transfer fails or is cancelled, the `.part` file is removed and the
+    /// destination is left untouched.
+    ///
+    /// If the Pylon is resumable, the `.part` file of a failed transfer is kept instead, along with a record of the
+    /// offer. Accepting the same offer into the same destination later on then only receives the rest of the file,
+    /// provided the sender Pylon confirms that the start of its file matches. The progress handler is first called with
+    /// the number of bytes that were already received.
+    ///
+    /// If the destination file already exists, the Pylon's [`CollisionPolicy`] decides what happens. Returns a report
+    /// with the path actually written to, or `None` if the transfer was skipped. Fails with [`PylonError::Cancelled`] if
+    /// the transfer was cancelled.
+    ///
+    /// The checksum of the received file is compared with the sender Pylon's, failing with
+    /// [`PylonError::IntegrityError`] if they differ.
+    ///
     /// # Arguments
     ///
     /// * `file` - The destination file path.
     /// * `progress_handler` - Callback function that accepts the number of bytes received and the total number of bytes
-    ///                        to receive.
-    /// * `cancel_handler` - Callback function to request cancellation of the file transfer.
-    pub async fn accept_transfer<F, P, T, C>(
+    ///   to receive.
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+    /// * `cancel_token` - Token to request cancellation of the file transfer.
+    pub async fn accept_transfer<F, P, T>(
         &mut self,
         file: F,
         progress_handler: Option<P>,
         transit_handler: Option<T>,
-        cancel_handler: Option<C>,
-    ) -> Result<(), PylonError>
+        cancel_token: Option<PylonCancelToken>,
+    ) -> Result<Option<TransferReport>, PylonError>
     where
         F: AsRef<Path>,
         P: FnMut(u64, u64) + 'static,
         T: FnMut(TransitInfo, SocketAddr) + 'static,
-        C: Future<Output = ()> + 'static,
     {
+        if let Some(e) = self.check_state(PylonState::OfferPending) {
+            return Err(e);
+        }
+
         // We're providing fallback/default handlers if the caller hasn't provided them.
         let transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)> =
-            get_transit_handler(transit_handler);
-        let progress_handler: Box<dyn FnMut(u64, u64)> = get_progress_handler(progress_handler);
-        let cancel_handler: Pin<Box<dyn Future<Output = ()>>> = get_cancel_handler(cancel_handler);
+            self.observe_transit(get_transit_handler(transit_handler));
+        let progress_handler: Box<dyn FnMut(u64, u64)> =
+            self.observe_progress(get_progress_handler(progress_handler));
+        let cancel_token = cancel_token.unwrap_or_default();
 
-        let mut file = File::create(&file)
-            .await
-            .map_err(|e| PylonError::Error(e.into()))?;
-        if let Some(r) = self.transfer_request.take() {
-            r.accept(transit_handler, progress_handler, &mut file, cancel_handler)
-                .await?;
+        let result = self
+            .receive_file(
+                file.as_ref(),
+                progress_handler,
+                transit_handler,
+                &cancel_token,
+            )
+            .await;
+        self.settle(result.as_ref().map(Option::is_some));
+
+        result
+    }
+
+    // TODO: add example(s)
+    /// Accepts an active transfer and writes the received data into the given sink.
+    ///
+    /// This allows receiving data somewhere other than the filesystem, e.g. into a socket or an in-memory buffer.
+    ///
+    /// Returns a report with the checksum of the received data, failing with [`PylonError::Cancelled`] if the transfer
+    /// was cancelled. The checksum is compared with the sender Pylon's, failing with [`PylonError::IntegrityError`] if
+    /// they differ.
+    ///
+    /// # Arguments
+    ///
+    /// * `sink` - The destination of the received data.
+    /// * `progress_handler` - Callback function that accepts the number of bytes received and the total number of bytes
+    ///   to receive.
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+    /// * `cancel_token` - Token to request cancellation of the transfer.
+    pub async fn accept_transfer_into<W, P, T>(
+        &mut self,
+        sink: &mut W,
+        progress_handler: Option<P>,
+        transit_handler: Option<T>,
+        cancel_token: Option<PylonCancelToken>,
+    ) -> Result<TransferReport, PylonError>
+    where
+        W: AsyncWrite + Unpin,
+        P: FnMut(u64, u64) + 'static,
+        T: FnMut(TransitInfo, SocketAddr) + 'static,
+    {
+        if let Some(e) = self.check_state(PylonState::OfferPending) {
+            return Err(e);
         }
 
-        return Err(PylonError::Error(
-            "There is currently no active transfer request".into(),
-        ));
+        // We're providing fallback/default handlers if the caller hasn't provided them.
+        let transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)> =
+            self.observe_transit(get_transit_handler(transit_handler));
+        let progress_handler: Box<dyn FnMut(u64, u64)> =
+            self.observe_progress(get_progress_handler(progress_handler));
+        let cancel_token = cancel_token.unwrap_or_default();
+
+        let result = match self.expect_offer(OfferKind::File) {
+            Ok(_) => self
+                .receive_into(
+                    protocol::Sink::Stream(sink),
+                    None,
+                    progress_handler,
+                    transit_handler,
+                    &cancel_token,
+                )
+                .await
+                .map(|transferred| TransferReport::new(None, transferred)),
+            Err(e) => Err(e),
+        };
+        self.settle(result.as_ref().map(|_| true));
+
+        result
+    }
+
+    // TODO: add example(s)
+    /// Accepts an active folder transfer and recreates the folder tree sent by the sender Pylon.
+    ///
+    /// The incoming tar archive is unpacked into the destination folder as it is received, preserving the relative
+    /// paths of its entries. If an entry would be unpacked outside of the destination folder, the transfer is aborted
+    /// with [`PylonError::PathTraversal`].
+    ///
+    /// If the destination folder already exists, the Pylon's [`CollisionPolicy`] decides what happens. Returns a report
+    /// with the path of the folder actually written to, or `None` if the transfer was skipped. Fails with
+    /// [`PylonError::Cancelled`] if the transfer was cancelled.
+    ///
+    /// # Arguments
+    ///
+    /// * `folder` - The destination folder path. It is created if it doesn't exist.
+    /// * `progress_handler` - Callback function that accepts the number of bytes received and the total number of bytes
+    ///   to receive. The totals are those of the whole archive, i.e. the contents of all files plus tar headers.
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+    /// * `cancel_token` - Token to request cancellation of the folder transfer.
+    pub async fn accept_folder_transfer<F, P, T>(
+        &mut self,
+        folder: F,
+        progress_handler: Option<P>,
+        transit_handler: Option<T>,
+        cancel_token: Option<PylonCancelToken>,
+    ) -> Result<Option<TransferReport>, PylonError>
+    where
+        F: AsRef<Path>,
+        P: FnMut(u64, u64) + 'static,
+        T: FnMut(TransitInfo, SocketAddr) + 'static,
+    {
+        if let Some(e) = self.check_state(PylonState::OfferPending) {
+            return Err(e);
+        }
+
+        // We're providing fallback/default handlers if the caller hasn't provided them.
+        let transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)> =
+            self.observe_transit(get_transit_handler(transit_handler));
+        let progress_handler: Box<dyn FnMut(u64, u64)> =
+            self.observe_progress(get_progress_handler(progress_handler));
+        let cancel_token = cancel_token.unwrap_or_default();
+
+        let result = self
+            .receive_folder(
+                folder.as_ref(),
+                progress_handler,
+                transit_handler,
+                &cancel_token,
+            )
+            .await;
+        self.settle(result.as_ref().map(Option::is_some));
+
+        result
+    }
+
+    // TODO: add example(s)
+    /// Accepts an active transfer and receives the offered file or folder into the given download folder.
+    ///
+    /// Unlike [`Pylon::accept_transfer`] and [`Pylon::accept_folder_transfer`], the destination is derived from the
+    /// name offered by the sender Pylon. That name is sanitized first: parent folder references, absolute paths,
+    /// control characters and reserved device names are stripped, so that nothing is written outside of the download
+    /// folder.
+    ///
+    /// Returns a report with the path of the received file or folder, or `None` if the transfer was skipped because of
+    /// the Pylon's [`CollisionPolicy`]. Fails with [`PylonError::Cancelled`] if the transfer was cancelled.
+    ///
+    /// # Arguments
+    ///
+    /// * `download_dir` - The folder to receive into. It is created if it doesn't exist.
+    /// * `progress_handler` - Callback function that accepts the number of bytes received and the total number of bytes
+    ///   to receive.
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+    /// * `cancel_token` - Token to request cancellation of the transfer.
+    pub async fn accept_transfer_to_dir<D, P, T>(
+        &mut self,
+        download_dir: D,
+        progress_handler: Option<P>,
+        transit_handler: Option<T>,
+        cancel_token: Option<PylonCancelToken>,
+    ) -> Result<Option<TransferReport>, PylonError>
+    where
+        D: AsRef<Path>,
+        P: FnMut(u64, u64) + 'static,
+        T: FnMut(TransitInfo, SocketAddr) + 'static,
+    {
+        if let Some(e) = self.check_state(PylonState::OfferPending) {
+            return Err(e);
+        }
+
+        // We're providing fallback/default handlers if the caller hasn't provided them.
+        let transit_handler: Box<dyn FnMut(TransitInfo, SocketAddr)> =
+            self.observe_transit(get_transit_handler(transit_handler));
+        let progress_handler: Box<dyn FnMut(u64, u64)> =
+            self.observe_progress(get_progress_handler(progress_handler));
+        let cancel_token = cancel_token.unwrap_or_default();
+
+        let result = self
+            .receive_to_dir(
+                download_dir.as_ref(),
+                progress_handler,
+                transit_handler,
+                &cancel_token,
+            )
+            .await;
+        self.settle(result.as_ref().map(Option::is_some));
+
+        result
     }
 
     /// Destroys the Pylon.
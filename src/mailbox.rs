@@ -0,0 +1,172 @@
+//! Mailboxes on the rendezvous server, opened by the sender Pylon for its wormhole code.
+//!
+//! The [`magic-wormhole`] library hands out the rendezvous server connection of a generated code only as part of the
+//! future that waits for the peer, so a code could only be given up on by dropping that connection. Opening the
+//! mailbox here keeps hold of the connection until the peer is awaited, so that an unused code can be released and its
+//! mailbox closed properly.
+//!
+//! [`magic-wormhole`]: https://crates.io/crates/magic-wormhole
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use magic_wormhole::rendezvous::RendezvousServer;
+use magic_wormhole::{AppConfig, AppID, Code, Wormhole, WormholeError};
+use smol::Timer;
+
+use crate::protocol::AppVersion;
+
+/// A mailbox that was opened for a code, and that the peer has yet to join.
+///
+/// If the code expires, the mailbox is closed as soon as it does, so that its nameplate is released even if the Pylon
+/// is left alone.
+pub(crate) struct Mailbox {
+    /// The connection to the rendezvous server, bound to the mailbox, until it is handed over or closed.
+    server: Arc<Mutex<Option<RendezvousServer>>>,
+    /// The app ID the connection is bound to.
+    appid: AppID,
+    /// The application version to exchange with the peer.
+    version: AppVersion,
+    /// The code, including the nameplate.
+    code: String,
+    /// The instant after which the code may no longer be used, if any.
+    expires: Option<Instant>,
+}
+
+impl Mailbox {
+    /// Connects to the rendezvous server, allocates a nameplate and opens its mailbox for a generated code.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The wormhole app config.
+    /// * `password` - The generated password of the code.
+    /// * `lifetime` - How long the code may be used for, if limited.
+    pub(crate) async fn allocate(
+        config: AppConfig<AppVersion>,
+        password: String,
+        lifetime: Option<Duration>,
+    ) -> Result<Self, WormholeError> {
+        let (mut server, _) = RendezvousServer::connect(&config.id, &config.rendezvous_url).await?;
+        let (nameplate, _) = server.allocate_claim_open().await?;
+        let code = format!("{}-{}", nameplate.0, password);
+
+        Ok(Self::new(server, config, code, lifetime))
+    }
+
+    /// Connects to the rendezvous server, and claims the nameplate of a code agreed on beforehand and opens its
+    /// mailbox.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The wormhole app config.
+    /// * `code` - The code, which must be valid.
+    /// * `lifetime` - How long the code may be used for, if limited.
+    pub(crate) async fn claim(
+        config: AppConfig<AppVersion>,
+        code: String,
+        lifetime: Option<Duration>,
+    ) -> Result<Self, WormholeError> {
+        let (mut server, _) = RendezvousServer::connect(&config.id, &config.rendezvous_url).await?;
+        server.claim_open(Code(code.clone()).nameplate()).await?;
+
+        Ok(Self::new(server, config, code, lifetime))
+    }
+
+    /// Returns a mailbox that was opened on the given connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `server` - The connection to the rendezvous server, bound to the mailbox.
+    /// * `config` - The wormhole app config.
+    /// * `code` - The code, including the nameplate.
+    /// * `lifetime` - How long the code may be used for, if limited.
+    fn new(
+        server: RendezvousServer,
+        config: AppConfig<AppVersion>,
+        code: String,
+        lifetime: Option<Duration>,
+    ) -> Self {
+        let server = Arc::new(Mutex::new(Some(server)));
+        let expires = lifetime.map(|lifetime| Instant::now() + lifetime);
+        if let Some(expires) = expires {
+            // The task only gets hold of the connection if it wasn't handed over or closed before the code expired.
+            let server = Arc::downgrade(&server);
+            smol::spawn(async move {
+                Timer::at(expires).await;
+                if let Some(server) = server.upgrade().and_then(|server| take(&server)) {
+                    let _ = shutdown(server).await;
+                }
+            })
+            .detach();
+        }
+
+        Self {
+            server,
+            appid: config.id,
+            version: config.app_version,
+            code,
+            expires,
+        }
+    }
+
+    /// Returns the code, including the nameplate.
+    pub(crate) fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// Returns how long the code may still be used for, or `None` if it doesn't expire.
+    pub(crate) fn lifetime(&self) -> Option<Duration> {
+        self.expires
+            .map(|expires| expires.saturating_duration_since(Instant::now()))
+    }
+
+    /// Returns whether the code expired.
+    pub(crate) fn expired(&self) -> bool {
+        self.lifetime() == Some(Duration::ZERO)
+    }
+
+    /// Returns a future that waits for the peer to join the mailbox, performs the key exchange, and yields the
+    /// established wormhole.
+    ///
+    /// Returns `None` if the mailbox was already closed because the code expired.
+    pub(crate) fn handshake(self) -> Option<impl Future<Output = Result<Wormhole, WormholeError>>> {
+        let server = take(&self.server)?;
+        Some(Wormhole::connect_custom(
+            server,
+            self.appid,
+            self.code,
+            self.version,
+        ))
+    }
+
+    /// Releases the nameplate and closes the mailbox, letting the rendezvous server know that the peer never showed up.
+    ///
+    /// Does nothing if the mailbox was already closed because the code expired.
+    pub(crate) async fn close(self) -> Result<(), WormholeError> {
+        match take(&self.server) {
+            Some(server) => shutdown(server).await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Takes the connection to the rendezvous server out of its slot, unless it was taken already.
+///
+/// # Arguments
+///
+/// * `server` - The slot of the connection.
+fn take(server: &Mutex<Option<RendezvousServer>>) -> Option<RendezvousServer> {
+    server.lock().unwrap().take()
+}
+
+/// Releases the nameplate and closes the mailbox that the given connection is bound to.
+///
+/// # Arguments
+///
+/// * `server` - The connection to the rendezvous server.
+async fn shutdown(server: RendezvousServer) -> Result<(), WormholeError> {
+    // The library doesn't export its mood type, so the mood is deserialized from its wire representation.
+    let lonely = serde_json::from_str("\"lonely\"")?;
+    Ok(server.shutdown(lonely).await?)
+}
@@ -0,0 +1,74 @@
+//! Listing the nameplates in use on the rendezvous server, so that a receiver Pylon can complete the nameplate of a
+//! code.
+//!
+//! This needs a websocket client of its own, and is only available with the `nameplates` feature, which is enabled by
+//! default.
+
+use async_tungstenite::tungstenite::Message;
+use futures::{SinkExt, StreamExt};
+use magic_wormhole::rendezvous::RendezvousError;
+use magic_wormhole::{AppConfig, WormholeError};
+use serde_json::{json, Value};
+
+use crate::protocol::AppVersion;
+
+/// Returns the nameplates that are currently in use on the rendezvous server.
+///
+/// The library doesn't expose listing the nameplates, so this speaks the client-server protocol on a connection of its
+/// own. Servers that require permission to connect, e.g. with a hashcash stamp, are not supported.
+///
+/// # Arguments
+///
+/// * `config` - The wormhole app config.
+pub(crate) async fn list(config: AppConfig<AppVersion>) -> Result<Vec<String>, WormholeError> {
+    let (mut ws, _) = async_tungstenite::async_std::connect_async(&*config.rendezvous_url)
+        .await
+        .map_err(RendezvousError::from)?;
+    let side = hex::encode(rand::random::<[u8; 5]>());
+    for message in [
+        json!({"type": "bind", "appid": config.id.0, "side": side}),
+        json!({"type": "list"}),
+    ] {
+        ws.send(Message::Text(message.to_string()))
+            .await
+            .map_err(RendezvousError::from)?;
+    }
+
+    while let Some(message) = ws.next().await {
+        let message: Value = match message.map_err(RendezvousError::from)? {
+            Message::Text(text) => serde_json::from_str(&text)?,
+            _ => continue,
+        };
+        match message["type"].as_str().unwrap_or_default() {
+            "welcome" => {
+                let permissions = &message["welcome"]["permission_required"];
+                if let Some(permissions) = permissions.as_object() {
+                    if !permissions.contains_key("none") {
+                        let methods = permissions.keys().cloned().collect();
+                        return Err(RendezvousError::Login(methods).into());
+                    }
+                }
+            }
+            "nameplates" => {
+                let nameplates = message["nameplates"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|nameplate| nameplate["id"].as_str().map(String::from))
+                    .collect();
+                let _ = ws.close(None).await;
+                return Ok(nameplates);
+            }
+            "error" => {
+                let error = message["error"].as_str().unwrap_or_default();
+                return Err(RendezvousError::Server(error.into()).into());
+            }
+            _ => continue,
+        }
+    }
+
+    Err(
+        RendezvousError::Protocol("Connection closed before the nameplates were listed".into())
+            .into(),
+    )
+}
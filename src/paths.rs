@@ -0,0 +1,120 @@
+//! Receive-side policy for turning names chosen by the sender into destination paths.
+//!
+//! Names of offers and of entries within folder archives come from the sender Pylon and must not be trusted. Offer
+//! names are sanitized into a single safe file name, whereas archive entries that would end up outside of the
+//! destination folder are refused outright, since silently rewriting them would change the shape of the tree.
+
+use std::path::{Component, Path, PathBuf};
+
+/// Names that Windows reserves for devices, regardless of their extension.
+const RESERVED_NAMES: [&str; 26] = [
+    "CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$", "COM0", "COM1", "COM2", "COM3", "COM4",
+    "COM5", "COM6", "COM7", "COM8", "COM9", "LPT0", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6",
+    "LPT7", "LPT8", "LPT9",
+];
+
+/// Characters that are not allowed in file names on at least one supported platform.
+const FORBIDDEN_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Sanitizes a single component of a name.
+///
+/// Control and forbidden characters are replaced, trailing dots and spaces (which Windows drops) are removed, and
+/// reserved device names are prefixed so that they refer to a regular file.
+fn sanitize_component(component: &str) -> String {
+    let component: String = component
+        .chars()
+        .map(|c| {
+            if c.is_control() || FORBIDDEN_CHARS.contains(&c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    let component = component.trim_end_matches(['.', ' ']);
+    let stem = component.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES
+        .iter()
+        .any(|r| r.eq_ignore_ascii_case(stem.trim_end()))
+    {
+        format!("_{}", component)
+    } else {
+        component.to_owned()
+    }
+}
+
+/// Returns a safe file name for a name offered by the sender Pylon, or `None` if nothing usable is left of it.
+///
+/// The name is split on both `/` and `\`, and only its last meaningful component is kept, so that parent directory
+/// references, absolute paths and drive prefixes are stripped.
+///
+/// # Arguments
+///
+/// * `name` - The name offered by the sender Pylon.
+pub(crate) fn sanitize_name(name: &str) -> Option<String> {
+    name.split(['/', '\\'])
+        .rfind(|c| !c.is_empty() && *c != "." && *c != "..")
+        .map(sanitize_component)
+        .filter(|c| !c.is_empty())
+}
+
+/// Returns the destination path of an offer within the given download folder, or `None` if the offered name is unusable.
+///
+/// # Arguments
+///
+/// * `download_dir` - The folder to confine the destination to.
+/// * `name` - The name offered by the sender Pylon.
+pub(crate) fn resolve(download_dir: &Path, name: &str) -> Option<PathBuf> {
+    sanitize_name(name).map(|name| download_dir.join(name))
+}
+
+/// Returns whether an entry of a received folder archive stays within the destination folder.
+///
+/// # Arguments
+///
+/// * `entry` - The path of the entry within the archive.
+pub(crate) fn is_confined(entry: &Path) -> bool {
+    entry
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// Returns the given path with a counter appended to its name, e.g. `name (1).ext`.
+///
+/// # Arguments
+///
+/// * `path` - The original path.
+/// * `counter` - The counter to append.
+fn numbered(path: &Path, counter: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{} ({}).{}", stem, counter, extension.to_string_lossy()),
+        None => format!("{} ({})", stem, counter),
+    };
+    path.with_file_name(name)
+}
+
+/// Returns whether anything (including a dangling symbolic link) exists at the given path.
+///
+/// # Arguments
+///
+/// * `path` - The path to check.
+pub(crate) async fn exists(path: &Path) -> bool {
+    smol::fs::symlink_metadata(path).await.is_ok()
+}
+
+/// Returns the first path of the form `name (n).ext` next to the given one that doesn't exist yet.
+///
+/// # Arguments
+///
+/// * `path` - The path that is already taken.
+pub(crate) async fn available(path: &Path) -> PathBuf {
+    let mut counter = 1;
+    loop {
+        let candidate = numbered(path, counter);
+        if !exists(&candidate).await {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
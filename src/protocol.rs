@@ -0,0 +1,1088 @@
+//! Our side of the (version 1) wormhole file transfer protocol.
+//!
+//! The underlying wormhole library only knows how to offer and receive single files, so we speak the protocol
+//! ourselves, on top of the library's wormhole and transit primitives. This lets us exchange text messages, offer
+//! folders as such, and resume interrupted file transfers between two Pylons.
+
+use std::future::Future;
+use std::io::{self, SeekFrom};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use magic_wormhole::transfer::TransferError;
+use magic_wormhole::transit::{Abilities, Hints, Transit, TransitConnector, TransitInfo};
+use magic_wormhole::{Wormhole, WormholeError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use smol::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use smol::Timer;
+
+use crate::consts::{CAPABILITIES_VERSION, VERSION};
+use crate::{Capabilities, OfferKind, PendingOffer, Timeouts, TransferPhase};
+
+/// Maximum duration that we are willing to wait for cleanup tasks to finish.
+const SHUTDOWN_TIME: Duration = Duration::from_secs(5);
+
+/// Maximum number of bytes sent in a single transit record.
+const RECORD_SIZE: usize = 4096;
+
+/// Archive format of the folders we offer. Other wormhole clients send zipped folders.
+const FOLDER_MODE: &str = "tar";
+
+/// The kinds of offers of the file transfer protocol, as named on the wire.
+const OFFERS: [&str; 3] = ["message", "file", "directory"];
+
+/// The checksum algorithm of the checksums sent over the wormhole after a file.
+const CHECKSUM: &str = "sha256";
+
+/// Error message sent to the sender when the receiver rejects its offer.
+pub(crate) const REJECTED: &str = "transfer rejected";
+
+/// Error message sent to the peer when the verifier of the wormhole is rejected.
+pub(crate) const VERIFIER_REJECTED: &str = "verifier rejected";
+
+/// Error message sent to the peer when the transfer is cancelled.
+const CANCELLED: &str = "transfer cancelled";
+
+/// Message exchanged with the peer over the wormhole.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum PeerMessage {
+    Offer(Offer),
+    Answer(Answer),
+    /// Tells the other side that we encountered an error.
+    Error(String),
+    /// Used to set up the transit connection.
+    Transit(TransitV1),
+    /// Tells the receiver from which offset the file will be sent, in response to [`Answer::FileResume`].
+    Resume {
+        offset: u64,
+    },
+    /// Tells the receiver the checksum of the file, once it has been sent.
+    Checksum {
+        sha256: String,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+/// What the sender offers to transfer.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Offer {
+    Message(String),
+    File {
+        filename: PathBuf,
+        filesize: u64,
+    },
+    Directory {
+        dirname: PathBuf,
+        mode: String,
+        zipsize: u64,
+        numbytes: u64,
+        numfiles: u64,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+impl Offer {
+    /// Returns the offer for a single file.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the file.
+    /// * `size` - The size of the file in bytes.
+    pub(crate) fn file(name: impl Into<PathBuf>, size: u64) -> Self {
+        Offer::File {
+            filename: name.into(),
+            filesize: size,
+        }
+    }
+
+    /// Returns the offer for a folder sent as a tar archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the folder.
+    /// * `archive_size` - The size of the archive in bytes.
+    /// * `num_bytes` - The total size of the files in the folder.
+    /// * `num_files` - The number of files in the folder.
+    pub(crate) fn folder(
+        name: impl Into<PathBuf>,
+        archive_size: u64,
+        num_bytes: u64,
+        num_files: u64,
+    ) -> Self {
+        Offer::Directory {
+            dirname: name.into(),
+            mode: FOLDER_MODE.into(),
+            zipsize: archive_size,
+            numbytes: num_bytes,
+            numfiles: num_files,
+        }
+    }
+}
+
+/// The receiver's answer to an offer.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Answer {
+    MessageAck(String),
+    FileAck(String),
+    /// Accepts a file, asking to continue after the given number of bytes, which the receiver already has.
+    FileResume {
+        offset: u64,
+        sha256: String,
+    },
+}
+
+/// Transit abilities and connection hints of one side.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct TransitV1 {
+    abilities_v1: Abilities,
+    hints_v1: Hints,
+}
+
+/// Acknowledgement sent over the transit connection once all data was received.
+#[derive(Debug, Deserialize, Serialize)]
+struct TransitAck {
+    ack: String,
+    sha256: String,
+}
+
+/// Application version exchanged during the wormhole handshake, advertising the protocol extensions we support.
+///
+/// Other wormhole clients send an empty object, so every extension must default to being unsupported. The `resume` and
+/// `checksum` flags predate the capability document, and are still sent for Pylons that don't know about it.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub(crate) struct AppVersion {
+    /// Whether interrupted file transfers can be resumed.
+    #[serde(default)]
+    resume: bool,
+    /// Whether the checksum of a file is sent over the wormhole after the file.
+    #[serde(default)]
+    checksum: bool,
+    /// What else we support, if we know about the capability document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    capabilities: Option<CapabilityDocument>,
+}
+
+/// The capability document carried by the application version, see [`Capabilities`].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct CapabilityDocument {
+    version: u32,
+    libpylon: String,
+    #[serde(default)]
+    offers: Vec<String>,
+    #[serde(default)]
+    compression: Vec<String>,
+    #[serde(default)]
+    checksums: Vec<String>,
+}
+
+impl AppVersion {
+    /// Returns the application version of this Pylon.
+    pub(crate) fn ours() -> Self {
+        AppVersion {
+            resume: true,
+            checksum: true,
+            capabilities: Some(CapabilityDocument {
+                version: CAPABILITIES_VERSION,
+                libpylon: VERSION.into(),
+                offers: OFFERS.iter().map(|offer| offer.to_string()).collect(),
+                compression: Vec::new(),
+                checksums: vec![CHECKSUM.into()],
+            }),
+        }
+    }
+
+    /// Returns the capabilities advertised by this application version.
+    pub(crate) fn capabilities(&self) -> Capabilities {
+        match &self.capabilities {
+            Some(document) => Capabilities {
+                version: document.version,
+                libpylon: Some(document.libpylon.clone()),
+                offers: document.offers.clone(),
+                compression: document.compression.clone(),
+                checksums: document.checksums.clone(),
+                resume: self.resume,
+            },
+            // Other wormhole clients, and Pylons that predate the capability document, support all offers of the file
+            // transfer protocol, and only the extensions they have a flag for.
+            None => Capabilities {
+                version: 0,
+                libpylon: None,
+                offers: OFFERS.iter().map(|offer| offer.to_string()).collect(),
+                compression: Vec::new(),
+                checksums: match self.checksum {
+                    true => vec![CHECKSUM.into()],
+                    false => Vec::new(),
+                },
+                resume: self.resume,
+            },
+        }
+    }
+}
+
+/// Returns the capabilities of the peer connected through the given wormhole.
+///
+/// # Arguments
+///
+/// * `wormhole` - The wormhole connected to the peer.
+pub(crate) fn peer_capabilities(wormhole: &Wormhole) -> Capabilities {
+    serde_json::from_value::<AppVersion>(wormhole.peer_version.clone())
+        .unwrap_or_default()
+        .capabilities()
+}
+
+/// Returns whether the peer connected through the given wormhole sends or expects the checksum of a file over the
+/// wormhole.
+///
+/// # Arguments
+///
+/// * `wormhole` - The wormhole connected to the peer.
+fn exchanges_checksum(wormhole: &Wormhole) -> bool {
+    peer_capabilities(wormhole)
+        .checksums
+        .iter()
+        .any(|checksum| checksum == CHECKSUM)
+}
+
+/// A readable and seekable source of data, see [`Source::Seekable`].
+pub(crate) trait ReadSeek: AsyncRead + AsyncSeek + Unpin {}
+
+impl<T: AsyncRead + AsyncSeek + Unpin> ReadSeek for T {}
+
+/// A writable and seekable destination of data, see [`Sink::Seekable`].
+pub(crate) trait WriteSeek: AsyncWrite + AsyncSeek + Unpin {}
+
+impl<T: AsyncWrite + AsyncSeek + Unpin> WriteSeek for T {}
+
+/// The contents sent by [`send`].
+pub(crate) enum Source<'a> {
+    /// Contents that are read from the start, and can be read again from any offset, which allows resuming.
+    Seekable(&'a mut dyn ReadSeek),
+    /// Contents that can only be read once.
+    Stream(&'a mut (dyn AsyncRead + Unpin)),
+}
+
+impl AsyncRead for Source<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Source::Seekable(source) => Pin::new(&mut **source).poll_read(cx, buf),
+            Source::Stream(source) => Pin::new(&mut **source).poll_read(cx, buf),
+        }
+    }
+}
+
+/// The destination of the contents received by [`ReceiveRequest::accept`].
+pub(crate) enum Sink<'a> {
+    /// A destination that can be written again from any offset, which allows resuming.
+    Seekable(&'a mut dyn WriteSeek),
+    /// A destination that can only be written once.
+    Stream(&'a mut (dyn AsyncWrite + Unpin)),
+}
+
+impl AsyncWrite for Sink<'_> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Sink::Seekable(sink) => Pin::new(&mut **sink).poll_write(cx, buf),
+            Sink::Stream(sink) => Pin::new(&mut **sink).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Sink::Seekable(sink) => Pin::new(&mut **sink).poll_flush(cx),
+            Sink::Stream(sink) => Pin::new(&mut **sink).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Sink::Seekable(sink) => Pin::new(&mut **sink).poll_close(cx),
+            Sink::Stream(sink) => Pin::new(&mut **sink).poll_close(cx),
+        }
+    }
+}
+
+/// The part of a file that the receiver already has, from an earlier, interrupted transfer.
+pub(crate) struct Resume {
+    /// The number of bytes already received.
+    pub(crate) offset: u64,
+    /// The hasher, having been fed the bytes already received.
+    pub(crate) hasher: Sha256,
+}
+
+/// Feeds the first `len` bytes of the source to a new SHA-256 hasher and returns it.
+///
+/// # Arguments
+///
+/// * `source` - The source to read from.
+/// * `len` - The number of bytes to read.
+pub(crate) async fn hash_prefix<R: AsyncRead + Unpin>(
+    source: &mut R,
+    len: u64,
+) -> io::Result<Sha256> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; RECORD_SIZE];
+    let mut remaining = len;
+    while remaining > 0 {
+        let n = source
+            .read(&mut buffer[..RECORD_SIZE.min(remaining as usize)])
+            .await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        hasher.update(&buffer[..n]);
+        remaining -= n as u64;
+    }
+    Ok(hasher)
+}
+
+/// What a completed transfer of a file (or zipped folder) amounted to.
+pub(crate) struct Transferred {
+    /// The SHA-256 checksum of the whole file.
+    pub(crate) sha256: Vec<u8>,
+    /// The number of bytes that went over the transit connection, which excludes a resumed part.
+    pub(crate) bytes: u64,
+    /// The time from establishing the transit connection until the peer confirmed the contents.
+    pub(crate) duration: Duration,
+    /// The kind of transit connection used.
+    pub(crate) transit: TransitInfo,
+    /// The socket address of the transit connection.
+    pub(crate) addr: SocketAddr,
+}
+
+/// An offer received from the sender.
+pub(crate) enum Incoming {
+    /// A text message. It is acknowledged as soon as it is received.
+    Text(String),
+    /// A file (or zipped folder) that must be accepted or rejected.
+    File(Box<ReceiveRequest>),
+}
+
+/// An offer as read off the wormhole, before the wormhole is handed over to a [`ReceiveRequest`].
+enum Offered {
+    Text(String),
+    File(TransitConnector, TransitV1, PendingOffer),
+}
+
+/// A pending file or folder offer from the sender.
+pub(crate) struct ReceiveRequest {
+    wormhole: Wormhole,
+    connector: TransitConnector,
+    their_abilities: Abilities,
+    their_hints: Arc<Hints>,
+    /// What is offered.
+    pub(crate) offer: PendingOffer,
+}
+
+/// Returns the error for a message that was not expected at this point of the protocol.
+///
+/// # Arguments
+///
+/// * `expected` - Description of the expected message.
+/// * `got` - The received message.
+fn unexpected_message(expected: &str, got: PeerMessage) -> TransferError {
+    TransferError::ProtocolUnexpectedMessage(expected.into(), Box::new(got))
+}
+
+/// The error wrapped in an I/O error when a phase of the transfer takes longer than allowed by its timeout.
+#[derive(Debug, thiserror::Error)]
+#[error("Timed out during the {0:?} phase")]
+pub(crate) struct Elapsed(TransferPhase);
+
+impl Elapsed {
+    /// Returns the I/O error for a phase of the transfer that timed out.
+    ///
+    /// # Arguments
+    ///
+    /// * `phase` - The phase that timed out.
+    fn error(phase: TransferPhase) -> io::Error {
+        io::Error::new(io::ErrorKind::TimedOut, Elapsed(phase))
+    }
+
+    /// Returns the phase that timed out, if the given I/O error is the result of a timeout.
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The I/O error.
+    pub(crate) fn phase(error: &io::Error) -> Option<TransferPhase> {
+        error
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<Elapsed>())
+            .map(|elapsed| elapsed.0)
+    }
+}
+
+/// Runs the future to completion, or until the timeout elapses, in which case `None` is returned.
+///
+/// # Arguments
+///
+/// * `duration` - The maximum duration of the future, if any.
+/// * `run` - The future to run.
+pub(crate) async fn timeout<T>(
+    duration: Option<Duration>,
+    run: impl Future<Output = T>,
+) -> Option<T> {
+    match duration {
+        Some(duration) => {
+            cancellable(run, async {
+                Timer::after(duration).await;
+            })
+            .await
+        }
+        None => Some(run.await),
+    }
+}
+
+/// Runs a fallible step of a transfer to completion, failing with an [`Elapsed`] error if it takes longer than
+/// allowed.
+///
+/// # Arguments
+///
+/// * `duration` - The maximum duration of the step, if any.
+/// * `phase` - The phase of the transfer the step is part of.
+/// * `run` - The step to run.
+async fn within<T, E>(
+    duration: Option<Duration>,
+    phase: TransferPhase,
+    run: impl Future<Output = Result<T, E>>,
+) -> Result<T, TransferError>
+where
+    E: Into<TransferError>,
+{
+    match timeout(duration, run).await {
+        Some(result) => result.map_err(Into::into),
+        None => Err(TransferError::IO(Elapsed::error(phase))),
+    }
+}
+
+/// Runs the future to completion, or until cancellation is requested, in which case `None` is returned.
+///
+/// # Arguments
+///
+/// * `run` - The future to run.
+/// * `cancel` - Future that resolves when cancellation is requested.
+async fn cancellable<T>(
+    run: impl Future<Output = T>,
+    cancel: impl Future<Output = ()>,
+) -> Option<T> {
+    smol::future::or(async { Some(run.await) }, async {
+        cancel.await;
+        None
+    })
+    .await
+}
+
+/// Closes the wormhole, giving up after [`SHUTDOWN_TIME`].
+///
+/// If the transfer failed on our side (or got cancelled), the peer is notified first.
+///
+/// # Arguments
+///
+/// * `wormhole` - The wormhole to close.
+/// * `error` - The reason for the failure, if the transfer failed on our side.
+async fn close(mut wormhole: Wormhole, error: Option<String>) {
+    let shutdown = async {
+        if let Some(error) = error {
+            let _ = wormhole.send_json(&PeerMessage::Error(error)).await;
+        }
+        let _ = wormhole.close().await;
+    };
+    smol::future::or(shutdown, async {
+        Timer::after(SHUTDOWN_TIME).await;
+    })
+    .await
+}
+
+/// Closes the wormhole after the result of a protocol step is known, notifying the peer of any failure.
+///
+/// Returns the result, or `None` if cancelled.
+///
+/// # Arguments
+///
+/// * `wormhole` - The wormhole to close.
+/// * `result` - The result of the protocol step, or `None` if it was cancelled.
+async fn finish<T>(
+    wormhole: Wormhole,
+    result: Option<Result<T, TransferError>>,
+) -> Result<Option<T>, TransferError> {
+    match result {
+        Some(Ok(value)) => {
+            close(wormhole, None).await;
+            Ok(Some(value))
+        }
+        // The peer already knows about errors that originated from it.
+        Some(Err(error @ TransferError::PeerError(_))) => {
+            close(wormhole, None).await;
+            Err(error)
+        }
+        Some(Err(error)) => {
+            close(wormhole, Some(error.to_string())).await;
+            Err(error)
+        }
+        None => {
+            close(wormhole, Some(CANCELLED.into())).await;
+            Ok(None)
+        }
+    }
+}
+
+/// Closes the wormhole before the transfer got underway, letting the peer know why.
+///
+/// # Arguments
+///
+/// * `wormhole` - The wormhole to close.
+/// * `error` - The reason for giving up on the transfer, or `None` if it was cancelled.
+pub(crate) async fn abort(wormhole: Wormhole, error: Option<String>) {
+    close(wormhole, Some(error.unwrap_or_else(|| CANCELLED.into()))).await
+}
+
+/// Sends a text message to the receiver.
+///
+/// Returns `None` if cancelled.
+///
+/// # Arguments
+///
+/// * `wormhole` - The wormhole connected to the receiver.
+/// * `text` - The message to send.
+/// * `cancel` - Future that resolves when cancellation is requested.
+pub(crate) async fn send_text(
+    mut wormhole: Wormhole,
+    text: &str,
+    cancel: impl Future<Output = ()>,
+) -> Result<Option<()>, TransferError> {
+    let run = async {
+        wormhole
+            .send_json(&PeerMessage::Offer(Offer::Message(text.into())))
+            .await?;
+        loop {
+            match wormhole.receive_json().await?? {
+                // The receiver sends its transit hints before it knows what is offered.
+                PeerMessage::Transit(_) => continue,
+                PeerMessage::Answer(Answer::MessageAck(ack)) if ack == "ok" => return Ok(()),
+                PeerMessage::Answer(Answer::MessageAck(_)) => return Err(TransferError::AckError),
+                PeerMessage::Error(error) => return Err(TransferError::PeerError(error)),
+                other => return Err(unexpected_message("answer/message_ack", other)),
+            }
+        }
+    };
+
+    let result = cancellable(run, cancel).await;
+    finish(wormhole, result).await
+}
+
+/// Sends the contents of a file over the transit connection, returning their SHA-256 checksum.
+///
+/// # Arguments
+///
+/// * `transit` - The transit connection.
+/// * `source` - The source of the contents. It must yield exactly `size - resume.offset` bytes.
+/// * `size` - The size of the file.
+/// * `resume` - The part of the file that the receiver already has.
+/// * `idle` - The maximum duration of sending a single record, if any.
+/// * `progress_handler` - Callback function that accepts the number of bytes sent and the total number of bytes.
+async fn send_records<R, P>(
+    transit: &mut Transit,
+    source: &mut R,
+    size: u64,
+    resume: Resume,
+    idle: Option<Duration>,
+    mut progress_handler: P,
+) -> Result<Vec<u8>, TransferError>
+where
+    R: AsyncRead + Unpin,
+    P: FnMut(u64, u64),
+{
+    let Resume {
+        offset: mut sent,
+        mut hasher,
+    } = resume;
+    let mut plaintext = vec![0; RECORD_SIZE];
+    progress_handler(sent, size);
+
+    loop {
+        let n = source.read(&mut plaintext).await?;
+        if n == 0 {
+            break;
+        }
+        within(
+            idle,
+            TransferPhase::Transfer,
+            transit.send_record(&plaintext[..n]),
+        )
+        .await?;
+        hasher.update(&plaintext[..n]);
+        sent += n as u64;
+        progress_handler(sent, size);
+        // As when receiving, a cancellation requested by the progress handler takes effect before the next record.
+        smol::future::yield_now().await;
+    }
+    within(idle, TransferPhase::Transfer, transit.flush()).await?;
+    if sent != size {
+        return Err(TransferError::FileSize {
+            sent_size: sent,
+            file_size: size,
+        });
+    }
+
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Checks whether the receiver's copy of the start of a file matches ours, and returns where to resume from.
+///
+/// The source is left positioned at the returned offset. If the source can't be resumed, or its start doesn't match
+/// the receiver's, the whole file is sent again.
+///
+/// # Arguments
+///
+/// * `source` - The source of the file, positioned at its start.
+/// * `size` - The size of the file.
+/// * `offset` - The number of bytes the receiver already has.
+/// * `sha256` - The hex-encoded SHA-256 checksum of the bytes the receiver already has.
+async fn resume_from(
+    source: &mut Source<'_>,
+    size: u64,
+    offset: u64,
+    sha256: &str,
+) -> io::Result<Resume> {
+    let restart = Resume {
+        offset: 0,
+        hasher: Sha256::new(),
+    };
+    let source = match source {
+        Source::Seekable(source) if offset <= size => source,
+        _ => return Ok(restart),
+    };
+    let hasher = hash_prefix(source, offset).await?;
+    if hex::encode(hasher.clone().finalize()) == sha256 {
+        Ok(Resume { offset, hasher })
+    } else {
+        source.seek(SeekFrom::Start(0)).await?;
+        Ok(restart)
+    }
+}
+
+/// Offers a file or folder to the receiver and, once accepted, sends its contents.
+///
+/// Returns what was transferred, or `None` if cancelled. Fails with [`TransferError::Checksum`] if the receiver got
+/// different contents.
+///
+/// # Arguments
+///
+/// * `wormhole` - The wormhole connected to the receiver.
+/// * `connector` - Our side of the transit connection.
+/// * `timeouts` - The limits on how long each phase of the transfer may take.
+/// * `offer` - What to offer.
+/// * `source` - The source of the contents to send. It must yield exactly `size` bytes. Only seekable sources can be
+///   resumed.
+/// * `size` - The number of bytes to send.
+/// * `accept_handler` - Callback function that is called once the receiver accepted the offer.
+/// * `transit_handler` - Callback function that accepts the transit information and socket address of the connection.
+/// * `progress_handler` - Callback function that accepts the number of bytes sent and the total number of bytes.
+/// * `cancel` - Future that resolves when cancellation is requested.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn send<A, G, P>(
+    mut wormhole: Wormhole,
+    connector: TransitConnector,
+    timeouts: Timeouts,
+    offer: Offer,
+    mut source: Source<'_>,
+    size: u64,
+    accept_handler: A,
+    transit_handler: G,
+    progress_handler: P,
+    cancel: impl Future<Output = ()>,
+) -> Result<Option<Transferred>, TransferError>
+where
+    A: FnOnce(),
+    G: FnOnce(TransitInfo, SocketAddr),
+    P: FnMut(u64, u64),
+{
+    let run = async {
+        wormhole
+            .send_json(&PeerMessage::Transit(TransitV1 {
+                abilities_v1: *connector.our_abilities(),
+                hints_v1: (**connector.our_hints()).clone(),
+            }))
+            .await?;
+        wormhole.send_json(&PeerMessage::Offer(offer)).await?;
+
+        let their_transit = match wormhole.receive_json().await?? {
+            PeerMessage::Transit(transit) => transit,
+            PeerMessage::Error(error) => return Err(TransferError::PeerError(error)),
+            other => return Err(unexpected_message("transit", other)),
+        };
+        let resume = match wormhole.receive_json().await?? {
+            PeerMessage::Answer(Answer::FileAck(ack)) if ack == "ok" => Resume {
+                offset: 0,
+                hasher: Sha256::new(),
+            },
+            PeerMessage::Answer(Answer::FileAck(_)) => return Err(TransferError::AckError),
+            // We only get asked to resume if we advertised it, see [`AppVersion`].
+            PeerMessage::Answer(Answer::FileResume { offset, sha256 }) => {
+                let resume = resume_from(&mut source, size, offset, &sha256).await?;
+                wormhole
+                    .send_json(&PeerMessage::Resume {
+                        offset: resume.offset,
+                    })
+                    .await?;
+                resume
+            }
+            PeerMessage::Error(error) => return Err(TransferError::PeerError(error)),
+            other => return Err(unexpected_message("answer/file_ack", other)),
+        };
+        accept_handler();
+
+        let transit_key = wormhole.key().derive_transit_key(wormhole.appid());
+        let connect = connector.leader_connect(
+            transit_key,
+            their_transit.abilities_v1,
+            Arc::new(their_transit.hints_v1),
+        );
+        let (mut transit, info, addr) =
+            within(timeouts.transit, TransferPhase::Transit, connect).await?;
+        let started = Instant::now();
+        let bytes = size - resume.offset;
+        transit_handler(info.clone(), addr);
+
+        let checksum = send_records(
+            &mut transit,
+            &mut source,
+            size,
+            resume,
+            timeouts.idle,
+            progress_handler,
+        )
+        .await?;
+        // Unlike the transit connection, the wormhole is authenticated, so the receiver can trust this checksum.
+        if exchanges_checksum(&wormhole) {
+            wormhole
+                .send_json(&PeerMessage::Checksum {
+                    sha256: hex::encode(&checksum),
+                })
+                .await?;
+        }
+        let ack = within(
+            timeouts.idle,
+            TransferPhase::Transfer,
+            transit.receive_record(),
+        )
+        .await?;
+        let ack: TransitAck = serde_json::from_slice(&ack)?;
+        if ack.ack != "ok" {
+            return Err(TransferError::AckError);
+        }
+        if ack.sha256 != hex::encode(&checksum) {
+            return Err(TransferError::Checksum);
+        }
+
+        Ok(Transferred {
+            sha256: checksum,
+            bytes,
+            duration: started.elapsed(),
+            transit: info,
+            addr,
+        })
+    };
+
+    let result = cancellable(run, cancel).await;
+    finish(wormhole, result).await
+}
+
+/// Waits for an offer from the sender.
+///
+/// Text messages are acknowledged right away, whereas file offers are returned as a [`ReceiveRequest`] to be accepted
+/// or rejected. Returns `None` if cancelled.
+///
+/// # Arguments
+///
+/// * `wormhole` - The wormhole connected to the sender.
+/// * `connector` - Our side of the transit connection.
+/// * `timeouts` - The limits on how long each phase of the transfer may take.
+/// * `cancel` - Future that resolves when cancellation is requested.
+pub(crate) async fn request(
+    mut wormhole: Wormhole,
+    connector: TransitConnector,
+    timeouts: Timeouts,
+    cancel: impl Future<Output = ()>,
+) -> Result<Option<Incoming>, TransferError> {
+    let run = async {
+        wormhole
+            .send_json(&PeerMessage::Transit(TransitV1 {
+                abilities_v1: *connector.our_abilities(),
+                hints_v1: (**connector.our_hints()).clone(),
+            }))
+            .await?;
+
+        // The sender's transit hints come before its offer, except for text messages which don't need any.
+        let mut their_transit = None;
+        let receive = async {
+            loop {
+                match wormhole.receive_json().await?? {
+                    PeerMessage::Transit(transit) => their_transit = Some(transit),
+                    PeerMessage::Offer(offer) => return Ok(offer),
+                    PeerMessage::Error(error) => return Err(TransferError::PeerError(error)),
+                    other => return Err(unexpected_message("transit or offer", other)),
+                }
+            }
+        };
+        let offer = within(timeouts.peer_wait, TransferPhase::PeerWait, receive).await?;
+
+        let offer = match offer {
+            Offer::Message(text) => {
+                wormhole
+                    .send_json(&PeerMessage::Answer(Answer::MessageAck("ok".into())))
+                    .await?;
+                return Ok(Offered::Text(text));
+            }
+            Offer::File { filename, filesize } => PendingOffer {
+                name: filename.to_string_lossy().into_owned(),
+                size: filesize,
+                kind: OfferKind::File,
+            },
+            Offer::Directory {
+                dirname,
+                mode,
+                zipsize,
+                ..
+            } if mode == FOLDER_MODE => PendingOffer {
+                name: dirname.to_string_lossy().into_owned(),
+                size: zipsize,
+                kind: OfferKind::Folder,
+            },
+            // Other wormhole clients send zipped folders, which can only be received as a single (zip) file.
+            Offer::Directory {
+                mut dirname,
+                zipsize,
+                ..
+            } => {
+                dirname.set_extension("zip");
+                PendingOffer {
+                    name: dirname.to_string_lossy().into_owned(),
+                    size: zipsize,
+                    kind: OfferKind::File,
+                }
+            }
+            Offer::Unknown => return Err(TransferError::UnsupportedOffer),
+        };
+        let transit = their_transit.ok_or_else(|| {
+            TransferError::Protocol("received a file offer without transit hints".into())
+        })?;
+
+        Ok(Offered::File(connector, transit, offer))
+    };
+
+    match cancellable(run, cancel).await {
+        Some(Ok(Offered::File(connector, transit, offer))) => {
+            Ok(Some(Incoming::File(Box::new(ReceiveRequest {
+                wormhole,
+                connector,
+                their_abilities: transit.abilities_v1,
+                their_hints: Arc::new(transit.hints_v1),
+                offer,
+            }))))
+        }
+        Some(Ok(Offered::Text(text))) => {
+            close(wormhole, None).await;
+            Ok(Some(Incoming::Text(text)))
+        }
+        Some(Err(error)) => finish(wormhole, Some(Err(error))).await,
+        None => finish(wormhole, None).await,
+    }
+}
+
+/// Receives the contents of a file over the transit connection, returning their SHA-256 checksum.
+///
+/// # Arguments
+///
+/// * `transit` - The transit connection.
+/// * `filesize` - The size of the file.
+/// * `resume` - The part of the file that we already have.
+/// * `idle` - The maximum duration of receiving a single record, if any.
+/// * `progress_handler` - Callback function that accepts the number of bytes received and the total number of bytes.
+/// * `content_handler` - The destination of the received bytes.
+async fn receive_records<P, W>(
+    transit: &mut Transit,
+    filesize: u64,
+    resume: Resume,
+    idle: Option<Duration>,
+    mut progress_handler: P,
+    content_handler: &mut W,
+) -> Result<Vec<u8>, TransferError>
+where
+    P: FnMut(u64, u64),
+    W: AsyncWrite + Unpin,
+{
+    let Resume {
+        offset: mut received,
+        mut hasher,
+    } = resume;
+    progress_handler(received, filesize);
+
+    while received < filesize {
+        let plaintext = within(idle, TransferPhase::Transfer, transit.receive_record()).await?;
+        received += plaintext.len() as u64;
+        if received > filesize {
+            return Err(TransferError::FileSize {
+                sent_size: received,
+                file_size: filesize,
+            });
+        }
+        content_handler.write_all(&plaintext).await?;
+        hasher.update(&plaintext);
+        progress_handler(received, filesize);
+        // Buffered records are received without ever waiting, so the cancel future gets a chance to run in between,
+        // e.g. for a cancellation requested by the progress handler. Otherwise, the transfer would be acknowledged.
+        smol::future::yield_now().await;
+    }
+    content_handler.flush().await?;
+
+    Ok(hasher.finalize().to_vec())
+}
+
+impl ReceiveRequest {
+    /// Accepts the offer and receives the file.
+    ///
+    /// If we already have the start of the file and the sender supports it, only the rest of the file is received.
+    ///
+    /// Returns what was transferred, or `None` if cancelled. If the sender supports it, the checksum of the file is
+    /// verified against the sender's, failing with [`TransferError::Checksum`] on mismatch.
+    ///
+    /// # Arguments
+    ///
+    /// * `transit_handler` - Callback function that accepts the transit information and socket address of the
+    ///   connection.
+    /// * `progress_handler` - Callback function that accepts the number of bytes received and the total number of bytes.
+    /// * `content_handler` - The destination of the received bytes. If resuming, it must be seekable and positioned
+    ///   after the part of the file that we already have.
+    /// * `resume` - The part of the file that we already have.
+    /// * `timeouts` - The limits on how long each phase of the transfer may take.
+    /// * `cancel` - Future that resolves when cancellation is requested.
+    pub(crate) async fn accept<G, P>(
+        self,
+        transit_handler: G,
+        progress_handler: P,
+        mut content_handler: Sink<'_>,
+        resume: Option<Resume>,
+        timeouts: Timeouts,
+        cancel: impl Future<Output = ()>,
+    ) -> Result<Option<Transferred>, TransferError>
+    where
+        G: FnOnce(TransitInfo, SocketAddr),
+        P: FnMut(u64, u64),
+    {
+        let ReceiveRequest {
+            mut wormhole,
+            connector,
+            their_abilities,
+            their_hints,
+            offer,
+        } = self;
+
+        let run = async {
+            let resume = match (resume, &mut content_handler) {
+                (Some(resume), Sink::Seekable(sink)) if peer_capabilities(&wormhole).resume => {
+                    let sha256 = hex::encode(resume.hasher.clone().finalize());
+                    wormhole
+                        .send_json(&PeerMessage::Answer(Answer::FileResume {
+                            offset: resume.offset,
+                            sha256,
+                        }))
+                        .await?;
+                    match wormhole.receive_json().await?? {
+                        PeerMessage::Resume { offset } if offset == resume.offset => resume,
+                        // The sender couldn't resume, so we start over.
+                        PeerMessage::Resume { offset: 0 } => {
+                            sink.seek(SeekFrom::Start(0)).await?;
+                            Resume {
+                                offset: 0,
+                                hasher: Sha256::new(),
+                            }
+                        }
+                        PeerMessage::Error(error) => return Err(TransferError::PeerError(error)),
+                        other => return Err(unexpected_message("resume", other)),
+                    }
+                }
+                (resume, sink) => {
+                    if let (Some(_), Sink::Seekable(sink)) = (resume, sink) {
+                        sink.seek(SeekFrom::Start(0)).await?;
+                    }
+                    wormhole
+                        .send_json(&PeerMessage::Answer(Answer::FileAck("ok".into())))
+                        .await?;
+                    Resume {
+                        offset: 0,
+                        hasher: Sha256::new(),
+                    }
+                }
+            };
+            let transit_key = wormhole.key().derive_transit_key(wormhole.appid());
+            let connect = connector.follower_connect(transit_key, their_abilities, their_hints);
+            let (mut transit, info, addr) =
+                within(timeouts.transit, TransferPhase::Transit, connect).await?;
+            let started = Instant::now();
+            let bytes = offer.size - resume.offset;
+            transit_handler(info.clone(), addr);
+
+            let checksum = receive_records(
+                &mut transit,
+                offer.size,
+                resume,
+                timeouts.idle,
+                progress_handler,
+                &mut content_handler,
+            )
+            .await?;
+            let ack = TransitAck {
+                ack: "ok".into(),
+                sha256: hex::encode(&checksum),
+            };
+            transit.send_record(&serde_json::to_vec(&ack)?).await?;
+
+            if exchanges_checksum(&wormhole) {
+                match wormhole.receive_json().await?? {
+                    PeerMessage::Checksum { sha256 } if sha256 == hex::encode(&checksum) => {}
+                    PeerMessage::Checksum { .. } => return Err(TransferError::Checksum),
+                    PeerMessage::Error(error) => return Err(TransferError::PeerError(error)),
+                    other => return Err(unexpected_message("checksum", other)),
+                }
+            }
+
+            Ok(Transferred {
+                sha256: checksum,
+                bytes,
+                duration: started.elapsed(),
+                transit: info,
+                addr,
+            })
+        };
+
+        let result = cancellable(run, cancel).await;
+        finish(wormhole, result).await
+    }
+
+    /// Rejects the offer, letting the sender know.
+    pub(crate) async fn reject(mut self) -> Result<(), WormholeError> {
+        self.wormhole
+            .send_json(&PeerMessage::Error(REJECTED.into()))
+            .await?;
+        self.wormhole.close().await
+    }
+}
@@ -0,0 +1,97 @@
+//! Records of interrupted file transfers, so that they can be resumed later.
+//!
+//! When a transfer into a `.part` file fails, the `.part` file is kept along with a record of what was being received
+//! and how much of it made it to disk. When the same offer is accepted again into the same destination, the record is
+//! checked against the `.part` file, and the sender is asked to continue where the transfer left off.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use smol::fs::File;
+
+use crate::protocol::{self, Resume};
+use crate::PendingOffer;
+
+/// Extension appended to the name of a `.part` file to get the name of its resume record.
+const RECORD_EXTENSION: &str = ".resume";
+
+/// What is persisted about an interrupted transfer.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResumeRecord {
+    /// The name of the offered file.
+    name: String,
+    /// The size of the offered file.
+    size: u64,
+    /// The number of bytes written to the `.part` file.
+    offset: u64,
+    /// The hex-encoded SHA-256 checksum of the bytes written to the `.part` file.
+    sha256: String,
+}
+
+/// Returns the path of the resume record of the given `.part` file.
+///
+/// # Arguments
+///
+/// * `part` - The path of the `.part` file.
+fn record_path(part: &Path) -> PathBuf {
+    let mut name = part.file_name().unwrap_or_default().to_os_string();
+    name.push(RECORD_EXTENSION);
+    part.with_file_name(name)
+}
+
+/// Records how much of the offer was written to the given `.part` file, so that the transfer can be resumed.
+///
+/// # Arguments
+///
+/// * `part` - The path of the `.part` file, which must have been flushed.
+/// * `offer` - The offer being received.
+pub(crate) async fn save(part: &Path, offer: &PendingOffer) -> io::Result<()> {
+    let offset = smol::fs::metadata(part).await?.len().min(offer.size);
+    let mut file = File::open(part).await?;
+    let hasher = protocol::hash_prefix(&mut file, offset).await?;
+    let record = ResumeRecord {
+        name: offer.name.clone(),
+        size: offer.size,
+        offset,
+        sha256: hex::encode(hasher.finalize()),
+    };
+    smol::fs::write(record_path(part), serde_json::to_vec(&record)?).await
+}
+
+/// Returns the part of the offer that was received into the given `.part` file by an earlier transfer.
+///
+/// Returns `None` if there is no record of an earlier transfer of the same offer, or if the `.part` file no longer
+/// matches the record.
+///
+/// # Arguments
+///
+/// * `part` - The path of the `.part` file.
+/// * `offer` - The offer being received.
+pub(crate) async fn load(part: &Path, offer: &PendingOffer) -> Option<Resume> {
+    let record = smol::fs::read(record_path(part)).await.ok()?;
+    let record: ResumeRecord = serde_json::from_slice(&record).ok()?;
+    if record.name != offer.name || record.size != offer.size {
+        return None;
+    }
+    let mut file = File::open(part).await.ok()?;
+    let hasher = protocol::hash_prefix(&mut file, record.offset).await.ok()?;
+    if hex::encode(hasher.clone().finalize()) != record.sha256 {
+        return None;
+    }
+    Some(Resume {
+        offset: record.offset,
+        hasher,
+    })
+}
+
+/// Removes the resume record of the given `.part` file, if any.
+///
+/// # Arguments
+///
+/// * `part` - The path of the `.part` file.
+pub(crate) async fn discard(part: &Path) {
+    let _ = smol::fs::remove_file(record_path(part)).await;
+}
@@ -0,0 +1,194 @@
+//! `wormhole-transfer:` URIs and QR codes, so that a code can be scanned rather than typed in, e.g. by a phone.
+//!
+//! The URI follows the scheme of the [`magic-wormhole`] library, with the code as its path and a non-default rendezvous
+//! server as its `rendezvous` parameter. A non-default relay server and app ID are carried in the `relay` and `appid`
+//! parameters, which other wormhole clients ignore.
+//!
+//! [`magic-wormhole`]: https://crates.io/crates/magic-wormhole
+
+use std::fmt;
+use std::str::FromStr;
+
+use magic_wormhole::rendezvous::DEFAULT_RENDEZVOUS_SERVER;
+use magic_wormhole::transfer::APPID;
+use magic_wormhole::transit::DEFAULT_RELAY_SERVER;
+use magic_wormhole::uri::{ParseError, WormholeTransferUri};
+use magic_wormhole::Code;
+use qrcode::render::{svg, unicode};
+use qrcode::{Color, QrCode};
+use url::Url;
+
+use crate::{PylonBuilder, PylonError};
+
+/// The number of modules of the quiet zone around a QR code.
+const QUIET_ZONE: usize = 4;
+
+/// A wormhole code, along with the non-default settings that the receiver Pylon needs to use it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodeUri {
+    /// The wormhole code.
+    pub code: String,
+    /// The URL of the rendezvous server, if it isn't the default one.
+    pub rendezvous_url: Option<String>,
+    /// The URL of the relay server, if it isn't the default one.
+    pub relay_url: Option<String>,
+    /// The app ID, if it isn't the one of the standard file transfer protocol.
+    pub id: Option<String>,
+}
+
+impl CodeUri {
+    /// Returns the URI for a code to be used with the given settings, leaving out those with default values.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The wormhole code.
+    /// * `rendezvous_url` - The URL of the rendezvous server.
+    /// * `relay_url` - The URL of the relay server.
+    /// * `id` - The app ID.
+    pub(crate) fn new(code: &str, rendezvous_url: &str, relay_url: &str, id: &str) -> Self {
+        let custom = |value: &str, default: &str| (value != default).then(|| value.to_string());
+        Self {
+            code: code.to_string(),
+            rendezvous_url: custom(rendezvous_url, DEFAULT_RENDEZVOUS_SERVER),
+            relay_url: custom(relay_url, DEFAULT_RELAY_SERVER),
+            id: custom(id, &APPID.0),
+        }
+    }
+
+    /// Returns a builder for a receiver Pylon, configured with the settings of the URI.
+    ///
+    /// Settings that are left out of the URI are set to their defaults, and the app ID to the one of the standard file
+    /// transfer protocol.
+    pub fn builder(&self) -> PylonBuilder {
+        let mut builder = PylonBuilder::default();
+        builder.id(self.id.clone().unwrap_or_else(|| APPID.0.to_string()));
+        if let Some(rendezvous_url) = &self.rendezvous_url {
+            builder.rendezvous_url(rendezvous_url.clone());
+        }
+        if let Some(relay_url) = &self.relay_url {
+            builder.relay_url(relay_url.clone());
+        }
+        builder
+    }
+
+    /// Returns the QR code of the URI.
+    fn qr_code(&self) -> Result<QrCode, PylonError> {
+        Ok(QrCode::new(self.to_string())?)
+    }
+
+    /// Renders the URI as a QR code in SVG format.
+    pub fn to_svg(&self) -> Result<String, PylonError> {
+        Ok(self.qr_code()?.render::<svg::Color>().build())
+    }
+
+    /// Renders the URI as a QR code in PNG format, in black and white, and returns the bytes of the image.
+    ///
+    /// # Arguments
+    ///
+    /// * `module_size` - The width and height of each module (i.e. square) of the QR code, in pixels.
+    pub fn to_png(&self, module_size: u32) -> Result<Vec<u8>, PylonError> {
+        let qr_code = self.qr_code()?;
+        let colors = qr_code.to_colors();
+        let width = qr_code.width();
+        let scale = module_size as usize;
+        let size = (width + 2 * QUIET_ZONE) * scale;
+
+        // Each row of the image packs a pixel per bit, with a set bit being white.
+        let row_len = (size + 7) / 8;
+        let mut pixels = vec![0xff; row_len * size];
+        for (i, color) in colors.iter().enumerate() {
+            if *color == Color::Light {
+                continue;
+            }
+            let (x, y) = (
+                (i % width + QUIET_ZONE) * scale,
+                (i / width + QUIET_ZONE) * scale,
+            );
+            for row in y..y + scale {
+                for column in x..x + scale {
+                    pixels[row * row_len + column / 8] &= !(0x80 >> (column % 8));
+                }
+            }
+        }
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, size as u32, size as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::One);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&pixels))
+            .map_err(std::io::Error::from)?;
+
+        Ok(png)
+    }
+
+    /// Renders the URI as a QR code made of Unicode block characters, e.g. to print it to a terminal.
+    ///
+    /// The QR code is drawn with light modules as blocks, for terminals with a dark background.
+    pub fn to_terminal(&self) -> Result<String, PylonError> {
+        Ok(self
+            .qr_code()?
+            .render::<unicode::Dense1x2>()
+            .dark_color(unicode::Dense1x2::Light)
+            .light_color(unicode::Dense1x2::Dark)
+            .build())
+    }
+}
+
+impl fmt::Display for CodeUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut url = Url::from(&WormholeTransferUri::new(Code(self.code.clone())));
+        let params = [
+            ("rendezvous", &self.rendezvous_url),
+            ("relay", &self.relay_url),
+            ("appid", &self.id),
+        ];
+        // Only add a query if there are any parameters, otherwise the URI ends with a `?`.
+        if params.iter().any(|(_, value)| value.is_some()) {
+            let mut query = url.query_pairs_mut();
+            for (name, value) in params {
+                if let Some(value) = value {
+                    query.append_pair(name, value);
+                }
+            }
+        }
+        url.fmt(f)
+    }
+}
+
+impl FromStr for CodeUri {
+    type Err = PylonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let url = Url::parse(s).map_err(ParseError::from)?;
+        let uri = WormholeTransferUri::try_from(&url)?;
+        // The URI of a code generated by the receiver can't be used, since a Pylon only generates codes to send.
+        if uri.is_leader {
+            return Err(ParseError::InvalidRole("leader".into()).into());
+        }
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+
+        Ok(Self {
+            code: uri.code.0,
+            rendezvous_url: param("rendezvous"),
+            relay_url: param("relay"),
+            id: param("appid"),
+        })
+    }
+}
+
+/// Parses a `wormhole-transfer:` URI, e.g. scanned from a QR code, and returns a builder for a receiver Pylon
+/// configured with its settings, along with the code to pass to [`crate::Pylon::request_transfer`].
+///
+/// # Arguments
+///
+/// * `uri` - The URI to parse.
+pub fn parse(uri: &str) -> Result<(PylonBuilder, String), PylonError> {
+    let uri: CodeUri = uri.parse()?;
+    Ok((uri.builder(), uri.code))
+}
@@ -0,0 +1,145 @@
+//! Short authentication strings, which let the users of two Pylons make sure that they are connected to each other.
+//!
+//! The key exchange derives a verifier from the shared key, which is the same on both sides unless someone managed to
+//! guess the code and got in between the Pylons. Comparing it rules that out. The verifier is rendered as a sequence of
+//! emoji, as in the SAS verification of [Matrix], and as a sequence of words of the PGP wordlist, either of which is
+//! short enough to be compared at a glance or read aloud.
+//!
+//! [Matrix]: https://spec.matrix.org/latest/client-server-api/#sas-method-emoji
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::wordlist::{PgpWordlist, Wordlist};
+
+/// The number of emoji of a verifier, each of which encodes 6 bits.
+const EMOJI_COUNT: usize = 7;
+
+/// The number of words of a verifier, each of which encodes a byte.
+const WORD_COUNT: usize = 5;
+
+/// An emoji of a verifier, along with its name, e.g. to read it aloud.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Emoji {
+    /// The emoji itself.
+    pub symbol: &'static str,
+    /// The English name of the emoji.
+    pub name: &'static str,
+}
+
+/// The verifier of a wormhole, as rendered for the users to compare, see [`crate::Pylon::verifier`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Verifier {
+    /// The verifier as a sequence of emoji.
+    pub emoji: Vec<Emoji>,
+    /// The verifier as a sequence of words.
+    pub words: Vec<String>,
+}
+
+impl Verifier {
+    /// Renders the verifier derived by the key exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The raw verifier, which must be at least 6 bytes long.
+    pub(crate) fn new(key: &[u8]) -> Self {
+        // The emoji take 6 bits each from the start of the verifier, most significant bits first.
+        let bits = key[..6]
+            .iter()
+            .fold(0u64, |bits, &byte| bits << 8 | byte as u64);
+        let emoji = (0..EMOJI_COUNT)
+            .map(|i| EMOJI[(bits >> (42 - 6 * i) & 0x3f) as usize])
+            .collect();
+        let words = key[..WORD_COUNT]
+            .iter()
+            .enumerate()
+            .map(|(i, &byte)| PgpWordlist.words(i)[byte as usize].to_string())
+            .collect();
+
+        Self { emoji, words }
+    }
+}
+
+impl fmt::Display for Verifier {
+    /// Writes the emoji of the verifier, separated by spaces.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbols: Vec<_> = self.emoji.iter().map(|emoji| emoji.symbol).collect();
+        f.write_str(&symbols.join(" "))
+    }
+}
+
+/// Defines the emoji that a verifier is rendered with, in the order of the values they encode.
+macro_rules! emoji {
+    ($($symbol:literal $name:literal),* $(,)?) => {
+        [$(Emoji { symbol: $symbol, name: $name }),*]
+    };
+}
+
+/// The emoji of the SAS verification of Matrix.
+const EMOJI: [Emoji; 64] = emoji![
+    "🐶" "Dog",
+    "🐱" "Cat",
+    "🦁" "Lion",
+    "🐎" "Horse",
+    "🦄" "Unicorn",
+    "🐷" "Pig",
+    "🐘" "Elephant",
+    "🐰" "Rabbit",
+    "🐼" "Panda",
+    "🐓" "Rooster",
+    "🐧" "Penguin",
+    "🐢" "Turtle",
+    "🐟" "Fish",
+    "🐙" "Octopus",
+    "🦋" "Butterfly",
+    "🌷" "Flower",
+    "🌳" "Tree",
+    "🌵" "Cactus",
+    "🍄" "Mushroom",
+    "🌏" "Globe",
+    "🌙" "Moon",
+    "☁️" "Cloud",
+    "🔥" "Fire",
+    "🍌" "Banana",
+    "🍎" "Apple",
+    "🍓" "Strawberry",
+    "🌽" "Corn",
+    "🍕" "Pizza",
+    "🎂" "Cake",
+    "❤️" "Heart",
+    "😀" "Smiley",
+    "🤖" "Robot",
+    "🎩" "Hat",
+    "👓" "Glasses",
+    "🔧" "Spanner",
+    "🎅" "Santa",
+    "👍" "Thumbs Up",
+    "☂️" "Umbrella",
+    "⌛" "Hourglass",
+    "⏰" "Clock",
+    "🎁" "Gift",
+    "💡" "Light Bulb",
+    "📕" "Book",
+    "✏️" "Pencil",
+    "📎" "Paperclip",
+    "✂️" "Scissors",
+    "🔒" "Lock",
+    "🔑" "Key",
+    "🔨" "Hammer",
+    "☎️" "Telephone",
+    "🏁" "Flag",
+    "🚂" "Train",
+    "🚲" "Bicycle",
+    "✈️" "Aeroplane",
+    "🚀" "Rocket",
+    "🏆" "Trophy",
+    "⚽" "Ball",
+    "🎸" "Guitar",
+    "🎺" "Trumpet",
+    "🔔" "Bell",
+    "⚓" "Anchor",
+    "🎧" "Headphones",
+    "📁" "Folder",
+    "📌" "Pin",
+];
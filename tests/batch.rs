@@ -0,0 +1,180 @@
+//! Tests for the per-file results of batch transfers.
+
+mod common;
+
+use common::{FileProgress, Progress, Transit};
+use libpylon::{PylonCancelToken, PylonError, PylonState};
+
+/// The size of each file of a batch that is cancelled halfway through.
+const LARGE_FILE_SIZE: usize = 1024 * 1024;
+
+#[test]
+fn unreadable_files_are_reported_and_left_out() {
+    let url = common::start();
+    let dir = common::work_dir("unreadable_files_are_reported_and_left_out");
+    std::fs::write(dir.join("one.txt"), b"one").unwrap();
+    std::fs::create_dir(dir.join("other")).unwrap();
+    std::fs::write(dir.join("other/one.txt"), b"clash").unwrap();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+
+    let files = [
+        dir.join("one.txt"),
+        dir.join("missing.txt"),
+        dir.join("other/one.txt"),
+    ];
+
+    let (sent, received) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| {
+            sender.start_batch_transfer(
+                files,
+                None::<Progress>,
+                None::<FileProgress>,
+                None::<Transit>,
+                None,
+            )
+        },
+        |receiver| {
+            receiver.accept_folder_transfer(
+                dir.join("received"),
+                None::<Progress>,
+                None::<Transit>,
+                None,
+            )
+        },
+    );
+
+    let results = sent.unwrap();
+    received.unwrap();
+    assert_eq!(results[0].bytes_sent, 3);
+    assert!(results[0].error.is_none());
+    assert!(matches!(results[1].error, Some(PylonError::Io(_))));
+    assert!(matches!(
+        results[2].error,
+        Some(PylonError::DuplicateName(_))
+    ));
+    assert_eq!(results[2].bytes_sent, 0);
+    assert_eq!(std::fs::read(dir.join("received/one.txt")).unwrap(), b"one");
+    assert_eq!(sender.state(), PylonState::Done);
+}
+
+#[test]
+fn failed_transfer_reports_sent_and_unsent_files() {
+    let url = common::start();
+    let dir = common::work_dir("failed_transfer_reports_sent_and_unsent_files");
+    let files: Vec<_> = (0..4).map(|i| dir.join(format!("{}.bin", i))).collect();
+    for file in &files {
+        std::fs::write(file, vec![1; LARGE_FILE_SIZE]).unwrap();
+    }
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+    let cancel_token = PylonCancelToken::new();
+    let cancel = cancel_token.clone();
+    // Cancels the transfer halfway through the second file. The sender may have sent a few more records by the time it
+    // notices, but nowhere near the remaining files.
+    let progress = move |sent: u64, _| {
+        if sent > (LARGE_FILE_SIZE + LARGE_FILE_SIZE / 2) as u64 {
+            cancel.cancel();
+        }
+    };
+
+    let (sent, received) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| {
+            sender.start_batch_transfer(
+                &files,
+                Some(progress),
+                None::<FileProgress>,
+                None::<Transit>,
+                Some(cancel_token),
+            )
+        },
+        |receiver| {
+            receiver.accept_folder_transfer(
+                dir.join("received"),
+                None::<Progress>,
+                None::<Transit>,
+                None,
+            )
+        },
+    );
+
+    let results = sent.unwrap();
+    assert!(received.is_err());
+    // The error goes to the file that was being sent, whichever that was.
+    let failed = results.iter().position(|r| r.error.is_some()).unwrap();
+    assert!(failed >= 1 && failed < results.len() - 1);
+    for result in &results[..failed] {
+        assert_eq!(result.bytes_sent, LARGE_FILE_SIZE as u64);
+        assert!(result.error.is_none());
+    }
+    assert!(results[failed].bytes_sent < LARGE_FILE_SIZE as u64);
+    assert!(matches!(results[failed].error, Some(PylonError::Cancelled)));
+    for result in &results[failed + 1..] {
+        assert_eq!(result.bytes_sent, 0);
+        assert!(matches!(result.error, Some(PylonError::BatchAborted)));
+        assert_eq!(result.error.as_ref().unwrap().code(), "batch_aborted");
+    }
+    assert_eq!(sender.state(), PylonState::Idle);
+}
+
+#[test]
+fn rejected_batch_fails() {
+    let url = common::start();
+    let dir = common::work_dir("rejected_batch_fails");
+    std::fs::write(dir.join("one.txt"), b"one").unwrap();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+
+    let (sent, rejected) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| {
+            sender.start_batch_transfer(
+                [dir.join("one.txt")],
+                None::<Progress>,
+                None::<FileProgress>,
+                None::<Transit>,
+                None,
+            )
+        },
+        |receiver| receiver.reject_transfer(),
+    );
+
+    rejected.unwrap();
+    assert!(matches!(sent, Err(PylonError::Rejected)));
+    assert_eq!(sender.state(), PylonState::Failed);
+}
+
+#[test]
+fn batch_cancelled_before_acceptance_fails() {
+    let url = common::start();
+    let dir = common::work_dir("batch_cancelled_before_acceptance_fails");
+    std::fs::write(dir.join("one.txt"), b"one").unwrap();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+    let cancel_token = PylonCancelToken::new();
+
+    let (sent, _) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| {
+            sender.start_batch_transfer(
+                [dir.join("one.txt")],
+                None::<Progress>,
+                None::<FileProgress>,
+                None::<Transit>,
+                Some(cancel_token.clone()),
+            )
+        },
+        // The offer is cancelled while it is still pending.
+        |_| async { cancel_token.cancel() },
+    );
+
+    assert!(matches!(sent, Err(PylonError::Cancelled)));
+    assert_eq!(sender.state(), PylonState::Idle);
+    assert_eq!(receiver.state(), PylonState::OfferPending);
+}
@@ -0,0 +1,128 @@
+//! Tests for cancelling transfers through a [`PylonCancelToken`] while data is being transferred.
+
+mod common;
+
+use common::{Progress, Transit};
+use libpylon::{Pylon, PylonCancelToken, PylonError, PylonEvent, PylonState};
+use smol::stream::StreamExt;
+
+/// The size of the sent file, large enough to take many round trips of the executor.
+const FILE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Cancels the token once the events of a Pylon report that half of the file was transferred.
+///
+/// # Arguments
+///
+/// * `pylon` - The Pylon whose events to watch.
+/// * `cancel_token` - The token to cancel.
+fn cancel_halfway(
+    pylon: &mut Pylon,
+    cancel_token: PylonCancelToken,
+) -> impl std::future::Future<Output = ()> {
+    let mut events = pylon.events();
+    async move {
+        while let Some(event) = events.next().await {
+            if let PylonEvent::Progress { bytes, .. } = event {
+                if bytes > (FILE_SIZE / 2) as u64 {
+                    cancel_token.cancel();
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Sends a file with the given cancel tokens, one of which is cancelled halfway through, and returns the results and
+/// final states of both Pylons.
+///
+/// # Arguments
+///
+/// * `name` - The name of the test.
+/// * `sender_token` - The cancel token of the sender Pylon.
+/// * `receiver_token` - The cancel token of the receiver Pylon.
+/// * `cancel_sender` - Whether the sender's token is cancelled halfway through, rather than the receiver's.
+fn transfer(
+    name: &str,
+    sender_token: PylonCancelToken,
+    receiver_token: PylonCancelToken,
+    cancel_sender: bool,
+) -> (
+    Result<(), PylonError>,
+    Result<(), PylonError>,
+    PylonState,
+    PylonState,
+) {
+    let url = common::start();
+    let dir = common::work_dir(name);
+    std::fs::write(dir.join("file.bin"), vec![1; FILE_SIZE]).unwrap();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+    let cancel = match cancel_sender {
+        true => cancel_halfway(&mut sender, sender_token.clone()),
+        false => cancel_halfway(&mut receiver, receiver_token.clone()),
+    };
+
+    let ((sent, _), received) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| {
+            let send = sender.start_transfer(
+                dir.join("file.bin"),
+                None::<Progress>,
+                None::<Transit>,
+                Some(sender_token),
+            );
+            smol::future::zip(send, cancel)
+        },
+        |receiver| {
+            receiver.accept_transfer(
+                dir.join("received.bin"),
+                None::<Progress>,
+                None::<Transit>,
+                Some(receiver_token),
+            )
+        },
+    );
+
+    assert!(!dir.join("received.bin").exists());
+    (
+        sent.map(|_| ()),
+        received.map(|_| ()),
+        sender.state(),
+        receiver.state(),
+    )
+}
+
+#[test]
+fn sender_cancels_mid_transfer() {
+    let sender_token = PylonCancelToken::new();
+    let (sent, received, sender_state, receiver_state) = transfer(
+        "sender_cancels_mid_transfer",
+        sender_token.clone(),
+        PylonCancelToken::new(),
+        true,
+    );
+
+    assert!(sender_token.is_cancelled());
+    assert!(matches!(sent, Err(PylonError::Cancelled)));
+    assert!(!matches!(received, Ok(_) | Err(PylonError::Cancelled)));
+    assert_eq!(sender_state, PylonState::Idle);
+    assert_eq!(receiver_state, PylonState::Failed);
+}
+
+#[test]
+fn receiver_cancels_mid_transfer() {
+    let receiver_token = PylonCancelToken::new();
+    let (sent, received, sender_state, receiver_state) = transfer(
+        "receiver_cancels_mid_transfer",
+        PylonCancelToken::new(),
+        receiver_token.clone(),
+        false,
+    );
+
+    assert!(receiver_token.is_cancelled());
+    assert!(matches!(received, Err(PylonError::Cancelled)));
+    assert!(!matches!(sent, Ok(_) | Err(PylonError::Cancelled)));
+    assert_eq!(sender_state, PylonState::Failed);
+    assert_eq!(receiver_state, PylonState::Idle);
+}
@@ -0,0 +1,81 @@
+//! Tests for the capabilities that Pylons exchange during the key exchange.
+
+mod common;
+
+use std::borrow::Cow;
+
+use libpylon::consts::{CAPABILITIES_VERSION, VERSION};
+use libpylon::Capabilities;
+use magic_wormhole::{AppConfig, AppID, Code, Wormhole};
+use serde_json::json;
+
+#[test]
+fn pylons_exchange_capabilities() {
+    let url = common::start();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+    assert_eq!(sender.peer_capabilities(), None);
+
+    let (sent, received) = smol::block_on(async {
+        let code = sender.gen_code(2).await.unwrap();
+        let send = sender.send_text("hello", None);
+        let receive = receiver.request_transfer(code, None);
+        smol::future::zip(send, receive).await
+    });
+
+    sent.unwrap();
+    received.unwrap();
+    let capabilities = sender.capabilities();
+    assert_eq!(capabilities.version, CAPABILITIES_VERSION);
+    assert_eq!(capabilities.libpylon.as_deref(), Some(VERSION));
+    assert_eq!(capabilities.offers, ["message", "file", "directory"]);
+    assert_eq!(capabilities.checksums, ["sha256"]);
+    assert!(capabilities.resume);
+    assert_eq!(sender.peer_capabilities(), Some(&capabilities));
+    assert_eq!(receiver.peer_capabilities(), Some(&capabilities));
+}
+
+#[test]
+fn peers_without_capability_document_are_negotiated_down() {
+    let url = common::start();
+    let mut sender = common::pylon(&url);
+    let config = AppConfig {
+        id: AppID(Cow::from("test.pylon/libpylon")),
+        rendezvous_url: Cow::from(url.clone()),
+        // As sent by a Pylon that predates the capability document and doesn't exchange checksums.
+        app_version: json!({ "resume": true }),
+    };
+
+    let sent = smol::block_on(async {
+        let code = sender.gen_code(2).await.unwrap();
+        let send = sender.send_text("unsent", None);
+        let receive = async {
+            let (_, mut wormhole) = Wormhole::connect_with_code(config, Code(code))
+                .await
+                .unwrap();
+            let error = json!({ "error": "declined" });
+            wormhole.send_json(&error).await.unwrap();
+            wormhole.close().await.unwrap();
+        };
+        smol::future::zip(send, receive).await.0
+    });
+
+    assert!(sent.is_err());
+    let peer = sender.peer_capabilities().unwrap();
+    assert_eq!(peer.version, 0);
+    assert_eq!(peer.libpylon, None);
+    assert!(peer.resume);
+    assert!(peer.checksums.is_empty());
+    let common = sender.capabilities().common(peer);
+    assert_eq!(
+        common,
+        Capabilities {
+            version: 0,
+            libpylon: None,
+            offers: vec!["message".into(), "file".into(), "directory".into()],
+            compression: Vec::new(),
+            checksums: Vec::new(),
+            resume: true,
+        }
+    );
+}
@@ -0,0 +1,301 @@
+//! Tests for abandoning and expiring wormhole codes, for codes agreed on beforehand, and for the code helpers.
+
+mod common;
+
+use std::time::Duration;
+
+use std::sync::Arc;
+
+use libpylon::wordlist::{DigitsWordlist, GermanWordlist, PgpWordlist, Wordlist};
+use libpylon::{code, PylonError, PylonState};
+
+const EXPIRY: Duration = Duration::from_millis(200);
+
+#[test]
+fn abandoned_code_can_be_replaced() {
+    let url = common::start();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+
+    let (abandoned, code, sent, received) = smol::block_on(async {
+        let abandoned = sender.gen_code(2).await.unwrap();
+        sender.abandon_code().await.unwrap();
+        assert_eq!(sender.state(), PylonState::Idle);
+
+        let code = sender.gen_code(2).await.unwrap();
+        let send = sender.send_text("hello", None);
+        let receive = receiver.request_transfer(code.clone(), None);
+        let (sent, received) = smol::future::zip(send, receive).await;
+        (abandoned, code, sent, received)
+    });
+
+    assert_ne!(abandoned, code);
+    sent.unwrap();
+    assert_eq!(received.unwrap(), Some("hello".into()));
+}
+
+#[test]
+fn abandon_code_requires_generated_code() {
+    let url = common::start();
+    let mut pylon = common::pylon(&url);
+
+    let abandoned = smol::block_on(pylon.abandon_code());
+
+    assert!(matches!(
+        abandoned,
+        Err(PylonError::InvalidState {
+            expected: PylonState::CodeGenerated,
+            actual: PylonState::Idle,
+        })
+    ));
+}
+
+#[test]
+fn code_expires_while_waiting_for_receiver() {
+    let url = common::start();
+    let mut sender = common::builder(&url)
+        .code_expiry(Some(EXPIRY))
+        .build()
+        .unwrap();
+
+    let sent = smol::block_on(async {
+        sender.gen_code(2).await.unwrap();
+        sender.send_text("unsent", None).await
+    });
+
+    let error = sent.unwrap_err();
+    assert!(matches!(error, PylonError::CodeExpired));
+    assert_eq!(error.code(), "code_expired");
+    assert_eq!(sender.state(), PylonState::Failed);
+    assert!(smol::block_on(sender.gen_code(2)).is_ok());
+}
+
+#[test]
+fn expired_code_is_replaced() {
+    let url = common::start();
+    let mut sender = common::builder(&url)
+        .code_expiry(Some(EXPIRY))
+        .build()
+        .unwrap();
+
+    let (expired, code) = smol::block_on(async {
+        let expired = sender.gen_code(2).await.unwrap();
+        smol::Timer::after(EXPIRY).await;
+        (expired, sender.gen_code(2).await.unwrap())
+    });
+
+    assert_ne!(expired, code);
+    assert_eq!(sender.state(), PylonState::CodeGenerated);
+}
+
+#[test]
+#[cfg(feature = "nameplates")]
+fn expired_code_releases_nameplate() {
+    let url = common::start();
+    let mut sender = common::builder(&url)
+        .code_expiry(Some(EXPIRY))
+        .build()
+        .unwrap();
+    let observer = common::pylon(&url);
+
+    let (code, listed) = smol::block_on(async {
+        let code = sender.gen_code(2).await.unwrap();
+        let listed = observer.list_nameplates().await.unwrap();
+        (code, listed)
+    });
+    let nameplate = code.split('-').next().unwrap().to_owned();
+    assert_eq!(listed, [nameplate.as_str()]);
+
+    // Nothing is asked of the sender, which still holds on to its code.
+    std::thread::sleep(EXPIRY * 2);
+    let listed = smol::block_on(observer.list_nameplates()).unwrap();
+    assert!(!listed.contains(&nameplate));
+    assert_eq!(sender.state(), PylonState::CodeGenerated);
+}
+
+#[test]
+fn agreed_code_connects_pylons() {
+    let url = common::start();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+
+    let (sent, received) = smol::block_on(async {
+        sender.use_code("7-orca-trumpet-7fq".into()).await.unwrap();
+        assert_eq!(sender.state(), PylonState::CodeGenerated);
+        let send = sender.send_text("hello", None);
+        let receive = receiver.request_transfer("7-orca-trumpet-7fq".into(), None);
+        smol::future::zip(send, receive).await
+    });
+
+    sent.unwrap();
+    assert_eq!(received.unwrap(), Some("hello".into()));
+}
+
+#[test]
+fn malformed_agreed_codes_are_refused() {
+    let url = common::start();
+    let mut pylon = common::pylon(&url);
+
+    for code in [
+        "orca-trumpet",
+        "x7-orca-trumpet",
+        "7-",
+        "7-orca--trumpet",
+        "7-orca trumpet",
+    ] {
+        let error = smol::block_on(pylon.use_code(code.into())).unwrap_err();
+        assert!(matches!(error, PylonError::InvalidCode(_)), "{}", code);
+        assert_eq!(error.code(), "code_invalid");
+    }
+    assert_eq!(pylon.state(), PylonState::Idle);
+}
+
+#[test]
+fn weak_agreed_codes_are_refused() {
+    let url = common::start();
+    let mut pylon = common::pylon(&url);
+
+    let error = smol::block_on(pylon.use_code("7-abc".into())).unwrap_err();
+    assert!(matches!(error, PylonError::WeakCode { .. }));
+    assert_eq!(error.code(), "code_weak");
+    // Repeated characters, sequences and repeated chunks are long but easy to guess.
+    for code in [
+        "7-aaaa",
+        "7-12345",
+        "7-zyxwvu",
+        "7-13579",
+        "7-abcabcabc",
+        "7-qzqzqzqz",
+    ] {
+        let error = smol::block_on(pylon.use_code(code.into())).unwrap_err();
+        assert!(matches!(error, PylonError::WeakCode { .. }), "{}", code);
+    }
+    // As long as they aren't, words that aren't in the wordlist count for each of their characters.
+    assert!(smol::block_on(pylon.use_code("7-x7qp".into())).is_ok());
+    smol::block_on(pylon.abandon_code()).unwrap();
+    // Two words of the wordlist are as strong as a generated code.
+    assert!(smol::block_on(pylon.use_code("7-adroitness-aardvark".into())).is_ok());
+}
+
+#[test]
+fn generated_codes_are_valid() {
+    let url = common::start();
+    let mut pylon = common::pylon(&url);
+
+    let code = smol::block_on(pylon.gen_code(3)).unwrap();
+
+    assert!(code::validate(&code, 3, &PgpWordlist).is_ok());
+    assert!(matches!(
+        code::validate(&code, 2, &PgpWordlist),
+        Err(PylonError::InvalidCode(_))
+    ));
+}
+
+#[test]
+fn misspelled_words_get_suggestions() {
+    let error = code::validate("7-adroitnes-aardvark", 2, &PgpWordlist).unwrap_err();
+
+    assert_eq!(error.code(), "code_unknown_word");
+    match error {
+        PylonError::UnknownCodeWord { word, suggestions } => {
+            assert_eq!(&*word, "adroitnes");
+            assert_eq!(suggestions[0], "adroitness");
+        }
+        error => panic!("unexpected error: {:?}", error),
+    }
+    // Transposed words are caught, since even and odd words alternate.
+    assert!(code::validate("7-aardvark-adroitness", 2, &PgpWordlist).is_err());
+}
+
+#[test]
+#[cfg(feature = "nameplates")]
+fn codes_complete_against_nameplates_and_wordlist() {
+    let url = common::start();
+    let mut sender = common::pylon(&url);
+    let receiver = common::pylon(&url);
+
+    let (code, nameplates) = smol::block_on(async {
+        let code = sender.gen_code(2).await.unwrap();
+        (code, receiver.list_nameplates().await.unwrap())
+    });
+
+    let nameplate = code.split('-').next().unwrap();
+    assert_eq!(nameplates, [nameplate]);
+    assert_eq!(
+        code::complete(&nameplate[..1], 2, &nameplates, &PgpWordlist),
+        [format!("{}-", nameplate)]
+    );
+    assert_eq!(
+        code::complete("7-adr", 2, &nameplates, &PgpWordlist),
+        ["7-adroitness-"]
+    );
+    assert_eq!(
+        code::complete("7-adroitness-aa", 2, &nameplates, &PgpWordlist),
+        ["7-adroitness-aardvark"]
+    );
+    assert!(code::complete("7-adroitness-aardvark-", 2, &nameplates, &PgpWordlist).is_empty());
+}
+
+/// A wordlist without any words at odd positions.
+struct HalfEmptyWordlist;
+
+impl Wordlist for HalfEmptyWordlist {
+    fn words(&self, position: usize) -> &[&str] {
+        match position % 2 {
+            0 => PgpWordlist.words(position),
+            _ => &[],
+        }
+    }
+}
+
+#[test]
+fn empty_wordlist_fails_code_generation() {
+    let url = common::start();
+    let mut sender = common::builder(&url)
+        .wordlist(Arc::new(HalfEmptyWordlist))
+        .build()
+        .unwrap();
+
+    let error = smol::block_on(sender.gen_code(2)).unwrap_err();
+    assert_eq!(error.code(), "codegen_failed");
+    assert_eq!(sender.state(), PylonState::Idle);
+}
+
+#[test]
+fn codes_from_other_wordlists_connect_pylons() {
+    let url = common::start();
+    let mut sender = common::builder(&url)
+        .wordlist(Arc::new(DigitsWordlist))
+        .build()
+        .unwrap();
+    let mut receiver = common::builder(&url)
+        .wordlist(Arc::new(DigitsWordlist))
+        .build()
+        .unwrap();
+
+    let (sent, received) = smol::block_on(async {
+        let code = sender.gen_code(2).await.unwrap();
+        assert!(code::validate(&code, 2, &DigitsWordlist).is_ok());
+        // As dictated over the phone.
+        let spaced: String = code.chars().flat_map(|c| [c, ' ']).collect();
+        let send = sender.send_text("hello", None);
+        let receive = receiver.request_transfer(spaced.replace(" - ", "-"), None);
+        smol::future::zip(send, receive).await
+    });
+
+    sent.unwrap();
+    assert_eq!(received.unwrap(), Some("hello".into()));
+}
+
+#[test]
+fn codes_are_normalized_against_wordlist() {
+    assert_eq!(
+        code::normalize(" 7-Löwe-KAFFEE ", &GermanWordlist),
+        "7-loewe-kaffee"
+    );
+    // Words outside of the wordlist are kept as typed.
+    assert_eq!(
+        code::normalize("7-Adroitness-Xy", &PgpWordlist),
+        "7-adroitness-Xy"
+    );
+}
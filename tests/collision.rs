@@ -0,0 +1,119 @@
+//! Tests for the collision policies, which decide what happens when the destination of a received file exists.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use common::{Progress, Transit};
+use libpylon::{CollisionPolicy, Pylon, PylonError, PylonState, TransferReport};
+
+/// The results of a transfer onto an existing file.
+struct Collision {
+    sent: Result<TransferReport, PylonError>,
+    received: Result<Option<TransferReport>, PylonError>,
+    receiver: Pylon,
+    destination: PathBuf,
+}
+
+/// Sends a file to a destination that already exists, with the given collision policy on the receiver's side.
+///
+/// If accepting the transfer fails, the transfer request is rejected so that the sender Pylon finishes too.
+///
+/// # Arguments
+///
+/// * `dir` - The folder to work in.
+/// * `policy` - The collision policy of the receiver Pylon.
+fn collide(dir: &Path, policy: CollisionPolicy) -> Collision {
+    let url = common::start();
+    let file = dir.join("new.txt");
+    let destination = dir.join("downloads/file.txt");
+    std::fs::write(&file, b"new").unwrap();
+    std::fs::create_dir_all(dir.join("downloads")).unwrap();
+    std::fs::write(&destination, b"old").unwrap();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::builder(&url)
+        .collision_policy(policy)
+        .build()
+        .unwrap();
+
+    let target = destination.clone();
+
+    let (sent, received) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| sender.start_transfer(&file, None::<Progress>, None::<Transit>, None),
+        |receiver| async move {
+            let received = receiver
+                .accept_transfer(target, None::<Progress>, None::<Transit>, None)
+                .await;
+            if received.is_err() {
+                assert_eq!(receiver.state(), PylonState::OfferPending);
+                receiver.reject_transfer().await.unwrap();
+            }
+            received
+        },
+    );
+
+    Collision {
+        sent,
+        received,
+        receiver,
+        destination,
+    }
+}
+
+#[test]
+fn overwrite_replaces_existing_file() {
+    let dir = common::work_dir("overwrite_replaces_existing_file");
+    let collision = collide(&dir, CollisionPolicy::Overwrite);
+
+    collision.sent.unwrap();
+    let received = collision.received.unwrap().unwrap();
+    assert_eq!(received.path.as_ref(), Some(&collision.destination));
+    assert_eq!(std::fs::read(&collision.destination).unwrap(), b"new");
+    assert_eq!(collision.receiver.state(), PylonState::Done);
+}
+
+#[test]
+fn fail_keeps_existing_file_and_transfer_request() {
+    let dir = common::work_dir("fail_keeps_existing_file_and_transfer_request");
+    let collision = collide(&dir, CollisionPolicy::Fail);
+
+    let error = collision.received.unwrap_err();
+    assert!(matches!(error, PylonError::DestinationExists(_)));
+    assert_eq!(error.code(), "destination_exists");
+    assert!(matches!(collision.sent, Err(PylonError::Rejected)));
+    assert_eq!(std::fs::read(&collision.destination).unwrap(), b"old");
+    assert_eq!(collision.receiver.state(), PylonState::Idle);
+}
+
+#[test]
+fn rename_receives_next_to_existing_file() {
+    let dir = common::work_dir("rename_receives_next_to_existing_file");
+    std::fs::create_dir_all(dir.join("downloads")).unwrap();
+    std::fs::write(dir.join("downloads/file (1).txt"), b"older").unwrap();
+    let collision = collide(&dir, CollisionPolicy::Rename);
+
+    collision.sent.unwrap();
+    let received = collision.received.unwrap().unwrap();
+    let renamed = dir.join("downloads/file (2).txt");
+    assert_eq!(received.path.as_ref(), Some(&renamed));
+    assert_eq!(std::fs::read(&renamed).unwrap(), b"new");
+    assert_eq!(std::fs::read(&collision.destination).unwrap(), b"old");
+    assert_eq!(
+        std::fs::read(dir.join("downloads/file (1).txt")).unwrap(),
+        b"older"
+    );
+    assert_eq!(collision.receiver.state(), PylonState::Done);
+}
+
+#[test]
+fn skip_rejects_transfer() {
+    let dir = common::work_dir("skip_rejects_transfer");
+    let collision = collide(&dir, CollisionPolicy::Skip);
+
+    assert!(collision.received.unwrap().is_none());
+    assert!(matches!(collision.sent, Err(PylonError::Rejected)));
+    assert_eq!(std::fs::read(&collision.destination).unwrap(), b"old");
+    assert_eq!(collision.receiver.state(), PylonState::Idle);
+}
@@ -0,0 +1,374 @@
+//! Helpers shared by the integration tests.
+//!
+//! The tests run pairs of Pylons against a minimal, in-process stand-in for the magic-wormhole rendezvous server, and
+//! force direct transit connections, so that they don't depend on any public servers.
+
+// Each test crate only uses some of the helpers.
+#![allow(dead_code)]
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error::Error;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use async_tungstenite::tungstenite::Message;
+use futures::{SinkExt, StreamExt};
+use libpylon::{Abilities, Pylon, PylonBuilder, TransitInfo};
+use magic_wormhole::{transit, AppConfig, AppID, Wormhole};
+use serde_json::{json, Value};
+use smol::channel::{unbounded, Sender};
+use smol::io::{AsyncReadExt, AsyncWriteExt};
+use smol::net::{TcpListener, TcpStream};
+
+/// A relay server that nobody listens on, since the transit connections are direct.
+pub const RELAY_URL: &str = "tcp://127.0.0.1:9";
+
+/// The type of a progress handler, to pass none to a transfer.
+pub type Progress = fn(u64, u64);
+/// The type of a per-file progress handler, to pass none to a batch transfer.
+pub type FileProgress = fn(usize, u64, u64);
+/// The type of a transit handler, to pass none to a transfer.
+pub type Transit = fn(TransitInfo, SocketAddr);
+
+/// Returns a builder for a Pylon that uses the rendezvous server at the given URL.
+///
+/// # Arguments
+///
+/// * `rendezvous_url` - The URL returned by [`start`].
+pub fn builder(rendezvous_url: &str) -> PylonBuilder {
+    let mut builder = PylonBuilder::default();
+    builder
+        .id("test.pylon/libpylon".into())
+        .rendezvous_url(rendezvous_url.into())
+        .relay_url(RELAY_URL.into())
+        .abilities(Abilities::FORCE_DIRECT);
+    builder
+}
+
+/// Returns a Pylon that uses the rendezvous server at the given URL.
+///
+/// # Arguments
+///
+/// * `rendezvous_url` - The URL returned by [`start`].
+pub fn pylon(rendezvous_url: &str) -> Pylon {
+    builder(rendezvous_url).build().unwrap()
+}
+
+/// Runs a file or folder transfer between two Pylons, returning the results of both sides.
+///
+/// The sender Pylon generates a code, with which the receiver Pylon requests the transfer while the sender offers it.
+///
+/// # Arguments
+///
+/// * `sender` - The sender Pylon.
+/// * `receiver` - The receiver Pylon.
+/// * `send` - Function that starts the transfer on the sender Pylon.
+/// * `receive` - Function that accepts or rejects the transfer on the receiver Pylon, once the offer is pending.
+pub fn connect<'a, S, R, SF, RF>(
+    sender: &'a mut Pylon,
+    receiver: &'a mut Pylon,
+    send: S,
+    receive: R,
+) -> (SF::Output, RF::Output)
+where
+    S: FnOnce(&'a mut Pylon) -> SF,
+    R: FnOnce(&'a mut Pylon) -> RF,
+    SF: Future,
+    RF: Future,
+{
+    smol::block_on(async move {
+        let code = sender.gen_code(2).await.unwrap();
+        let receive = async move {
+            assert_eq!(receiver.request_transfer(code, None).await.unwrap(), None);
+            receive(receiver).await
+        };
+        smol::future::zip(send(sender), receive).await
+    })
+}
+
+/// Returns a new, empty folder for a test to work in.
+///
+/// # Arguments
+///
+/// * `name` - The name of the test.
+pub fn work_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join("libpylon-tests").join(format!(
+        "{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Offers the given data over a new wormhole, speaking the file transfer protocol directly rather than through a Pylon,
+/// e.g. to send what a Pylon never would.
+///
+/// Returns the code of the wormhole, along with a future that sends the offer and, once the receiver accepted it, the
+/// data. The future fails if the receiver rejects the offer or doesn't acknowledge the data.
+///
+/// # Arguments
+///
+/// * `rendezvous_url` - The URL returned by [`start`].
+/// * `offer` - The offer, as sent on the wire, e.g. `{"file": {"filename": "a.txt", "filesize": 1}}`.
+/// * `data` - The data to send over the transit connection.
+/// * `checksum` - The hex-encoded SHA-256 checksum to send over the wormhole once the data is sent, if any. Only if
+///   given, the receiver is told that checksums are exchanged.
+pub async fn raw_sender(
+    rendezvous_url: &str,
+    offer: Value,
+    data: Vec<u8>,
+    checksum: Option<String>,
+) -> (String, impl Future<Output = Result<(), Box<dyn Error>>>) {
+    let config = AppConfig {
+        id: AppID(Cow::from("test.pylon/libpylon")),
+        rendezvous_url: Cow::from(rendezvous_url.to_owned()),
+        app_version: json!({ "checksum": checksum.is_some() }),
+    };
+    let (welcome, wormhole) = Wormhole::connect_without_code(config, 2).await.unwrap();
+    let send = async move {
+        let mut wormhole = wormhole.await?;
+        let connector = transit::init(Abilities::FORCE_DIRECT, None, Vec::new()).await?;
+        let transit = json!({
+            "abilities-v1": connector.our_abilities(),
+            "hints-v1": **connector.our_hints(),
+        });
+        wormhole.send_json(&json!({ "transit": transit })).await?;
+        wormhole.send_json(&json!({ "offer": offer })).await?;
+
+        let their_transit: Value = wormhole.receive_json().await??;
+        let answer: Value = wormhole.receive_json().await??;
+        if answer["answer"]["file_ack"] != "ok" {
+            return Err(format!("offer not accepted: {}", answer).into());
+        }
+        let key = wormhole.key().derive_transit_key(wormhole.appid());
+        let abilities = serde_json::from_value(their_transit["transit"]["abilities-v1"].clone())?;
+        let hints = serde_json::from_value(their_transit["transit"]["hints-v1"].clone())?;
+        let (mut transit, _, _) = connector
+            .leader_connect(key, abilities, Arc::new(hints))
+            .await?;
+        for record in data.chunks(4096) {
+            transit.send_record(record).await?;
+        }
+        transit.flush().await?;
+        if let Some(sha256) = checksum {
+            let checksum = json!({ "checksum": { "sha256": sha256 } });
+            wormhole.send_json(&checksum).await?;
+        }
+        transit.receive_record().await?;
+        wormhole.close().await?;
+        Ok(())
+    };
+    (welcome.code.0, send)
+}
+
+/// Starts a transit relay server on a random local port and returns its URL.
+///
+/// Like the real one, it pairs up the two connections that ask to relay the same token, and then forwards whatever one
+/// of them sends to the other.
+pub fn start_relay() -> String {
+    let listener = smol::block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+    let url = format!("tcp://{}", listener.local_addr().unwrap());
+    let waiting = Arc::new(Mutex::new(HashMap::new()));
+    std::thread::spawn(move || {
+        smol::block_on(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                smol::spawn(relay(stream, waiting.clone())).detach();
+            }
+        })
+    });
+    url
+}
+
+/// Serves a single connection to the relay server.
+///
+/// # Arguments
+///
+/// * `stream` - The connection.
+/// * `waiting` - The connections waiting for their counterpart, by token.
+async fn relay(mut stream: TcpStream, waiting: Arc<Mutex<HashMap<String, TcpStream>>>) {
+    // The request is a single line: `please relay <token> for side <side>`.
+    let mut request = Vec::new();
+    let mut byte = [0];
+    while byte != *b"\n" {
+        if stream.read_exact(&mut byte).await.is_err() {
+            return;
+        }
+        request.push(byte[0]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let token = match request.split(' ').nth(2) {
+        Some(token) => token.to_owned(),
+        None => return,
+    };
+
+    let other = {
+        let mut waiting = waiting.lock().unwrap();
+        match waiting.remove(&token) {
+            Some(other) => other,
+            None => {
+                waiting.insert(token, stream);
+                return;
+            }
+        }
+    };
+    for mut side in [stream.clone(), other.clone()] {
+        let _ = side.write_all(b"ok\n").await;
+    }
+    let forward = smol::io::copy(stream.clone(), other.clone());
+    let backward = smol::io::copy(other, stream);
+    let _ = smol::future::zip(forward, backward).await;
+}
+
+/// A mailbox, along with the connections that have it open.
+#[derive(Default)]
+struct Mailbox {
+    messages: Vec<Value>,
+    subscribers: Vec<(u64, Sender<Value>)>,
+}
+
+/// The state of the rendezvous server, shared by all connections.
+#[derive(Default)]
+struct State {
+    next_nameplate: u64,
+    next_connection: u64,
+    nameplates: HashMap<String, String>,
+    mailboxes: HashMap<String, Mailbox>,
+}
+
+/// Starts the rendezvous server on a random local port and returns its websocket URL.
+pub fn start() -> String {
+    let listener = smol::block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+    let url = format!("ws://{}/v1", listener.local_addr().unwrap());
+    let state = Arc::new(Mutex::new(State::default()));
+    std::thread::spawn(move || {
+        smol::block_on(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                smol::spawn(serve(stream, state.clone())).detach();
+            }
+        })
+    });
+    url
+}
+
+/// Serves a single websocket connection.
+async fn serve(stream: TcpStream, state: Arc<Mutex<State>>) {
+    let ws = match async_tungstenite::accept_async(stream).await {
+        Ok(ws) => ws,
+        Err(_) => return,
+    };
+    let (mut sink, mut source) = ws.split();
+    let (tx, rx) = unbounded::<Value>();
+    smol::spawn(async move {
+        while let Ok(message) = rx.recv().await {
+            if sink.send(Message::Text(message.to_string())).await.is_err() {
+                break;
+            }
+        }
+    })
+    .detach();
+
+    let _ = tx.send(json!({"type": "welcome", "welcome": {}})).await;
+    let id = {
+        let mut state = state.lock().unwrap();
+        state.next_connection += 1;
+        state.next_connection
+    };
+    let mut side = Value::Null;
+    let mut opened = None;
+    while let Some(Ok(message)) = source.next().await {
+        let message: Value = match message {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let _ = tx.send(json!({"type": "ack"})).await;
+        let replies = handle(&state, &tx, id, &mut side, &mut opened, &message);
+        for reply in replies {
+            let _ = tx.send(reply).await;
+        }
+    }
+}
+
+/// Handles a single message from a connection, returning the replies to it.
+fn handle(
+    state: &Mutex<State>,
+    tx: &Sender<Value>,
+    id: u64,
+    side: &mut Value,
+    opened: &mut Option<String>,
+    message: &Value,
+) -> Vec<Value> {
+    let mut state = state.lock().unwrap();
+    match message["type"].as_str().unwrap_or_default() {
+        "bind" => {
+            *side = message["side"].clone();
+            vec![]
+        }
+        "list" => {
+            let nameplates: Vec<Value> = state
+                .nameplates
+                .keys()
+                .map(|id| json!({ "id": id }))
+                .collect();
+            vec![json!({"type": "nameplates", "nameplates": nameplates})]
+        }
+        "allocate" => {
+            state.next_nameplate += 1;
+            let nameplate = state.next_nameplate.to_string();
+            vec![json!({"type": "allocated", "nameplate": nameplate})]
+        }
+        "claim" => {
+            let nameplate = message["nameplate"].as_str().unwrap().to_owned();
+            let mailbox = state
+                .nameplates
+                .entry(nameplate.clone())
+                .or_insert_with(|| format!("mailbox-{}", nameplate))
+                .clone();
+            vec![json!({"type": "claimed", "mailbox": mailbox})]
+        }
+        "release" => {
+            if let Some(nameplate) = message["nameplate"].as_str() {
+                state.nameplates.remove(nameplate);
+            }
+            vec![json!({"type": "released"})]
+        }
+        "open" => {
+            let name = message["mailbox"].as_str().unwrap().to_owned();
+            let mailbox = state.mailboxes.entry(name.clone()).or_default();
+            mailbox.subscribers.push((id, tx.clone()));
+            *opened = Some(name);
+            mailbox.messages.clone()
+        }
+        "add" => {
+            let mailbox = opened.as_ref().and_then(|m| state.mailboxes.get_mut(m));
+            if let Some(mailbox) = mailbox {
+                let message = json!({
+                    "type": "message",
+                    "side": side,
+                    "phase": message["phase"],
+                    "body": message["body"],
+                });
+                mailbox.messages.push(message.clone());
+                for (_, subscriber) in &mailbox.subscribers {
+                    let _ = subscriber.try_send(message.clone());
+                }
+            }
+            vec![]
+        }
+        "close" => {
+            if let Some(mailbox) = opened.take().and_then(|m| state.mailboxes.get_mut(&m)) {
+                mailbox.subscribers.retain(|(s, _)| *s != id);
+            }
+            vec![json!({"type": "closed"})]
+        }
+        "ping" => vec![json!({"type": "pong", "pong": message["ping"]})],
+        _ => vec![],
+    }
+}
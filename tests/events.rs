@@ -0,0 +1,172 @@
+//! Tests for the order of the events that Pylons emit over the course of a transfer.
+
+mod common;
+
+use common::{Progress, Transit};
+use libpylon::{PylonCancelToken, PylonError, PylonEvent};
+use smol::stream::StreamExt;
+
+/// The size of the sent file, large enough to be sent in many records.
+const FILE_SIZE: usize = 256 * 1024;
+
+/// Returns the kinds of the given events, in order, with consecutive progress events collapsed into one.
+///
+/// # Arguments
+///
+/// * `events` - The events to summarize.
+fn kinds(events: &[PylonEvent]) -> Vec<&'static str> {
+    let mut kinds = Vec::new();
+    for event in events {
+        let kind = match event {
+            PylonEvent::CodeAllocated { .. } => "code",
+            PylonEvent::PeerConnected => "peer",
+            PylonEvent::VerifierReady { .. } => "verifier",
+            PylonEvent::TransitEstablished { .. } => "transit",
+            PylonEvent::Progress { .. } => "progress",
+            PylonEvent::Completed => "completed",
+            PylonEvent::Failed { .. } => "failed",
+            PylonEvent::Cancelled => "cancelled",
+            PylonEvent::Skipped => "skipped",
+            PylonEvent::Retrying { .. } => "retrying",
+        };
+        if kinds.last() != Some(&kind) || kind != "progress" {
+            kinds.push(kind);
+        }
+    }
+    kinds
+}
+
+/// Returns the progress reported by the given events, in order.
+///
+/// # Arguments
+///
+/// * `events` - The events to take the progress from.
+fn progress(events: &[PylonEvent]) -> Vec<(u64, u64)> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            PylonEvent::Progress { bytes, total } => Some((*bytes, *total)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn transfer_events_arrive_in_order() {
+    let url = common::start();
+    let dir = common::work_dir("transfer_events_arrive_in_order");
+    std::fs::write(dir.join("file.bin"), vec![1; FILE_SIZE]).unwrap();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+    let sender_events = sender.events();
+    let receiver_events = receiver.events();
+
+    let (sent, received) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| {
+            sender.start_transfer(
+                dir.join("file.bin"),
+                None::<Progress>,
+                None::<Transit>,
+                None,
+            )
+        },
+        |receiver| {
+            receiver.accept_transfer(
+                dir.join("received.bin"),
+                None::<Progress>,
+                None::<Transit>,
+                None,
+            )
+        },
+    );
+
+    sent.unwrap();
+    received.unwrap();
+    drop(sender);
+    drop(receiver);
+    let sender_events: Vec<PylonEvent> = smol::block_on(sender_events.collect());
+    let receiver_events: Vec<PylonEvent> = smol::block_on(receiver_events.collect());
+    assert_eq!(
+        kinds(&sender_events),
+        [
+            "code",
+            "peer",
+            "verifier",
+            "transit",
+            "progress",
+            "completed"
+        ]
+    );
+    assert_eq!(
+        kinds(&receiver_events),
+        ["peer", "verifier", "transit", "progress", "completed"]
+    );
+    for events in [&sender_events, &receiver_events] {
+        let progress = progress(events);
+        assert!(progress.windows(2).all(|p| p[0].0 <= p[1].0));
+        assert_eq!(progress.last(), Some(&(FILE_SIZE as u64, FILE_SIZE as u64)));
+    }
+}
+
+#[test]
+fn cancelled_transfer_ends_with_cancelled_event() {
+    let url = common::start();
+    let dir = common::work_dir("cancelled_transfer_ends_with_cancelled_event");
+    std::fs::write(dir.join("file.bin"), vec![1; FILE_SIZE]).unwrap();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+    let sender_events = sender.events();
+    let receiver_events = receiver.events();
+    let cancel_token = PylonCancelToken::new();
+    let cancel = cancel_token.clone();
+    let progress = move |sent: u64, _| {
+        if sent > (FILE_SIZE / 2) as u64 {
+            cancel.cancel();
+        }
+    };
+
+    let (sent, received) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| {
+            sender.start_transfer(
+                dir.join("file.bin"),
+                Some(progress),
+                None::<Transit>,
+                Some(cancel_token),
+            )
+        },
+        |receiver| {
+            receiver.accept_transfer(
+                dir.join("received.bin"),
+                None::<Progress>,
+                None::<Transit>,
+                None,
+            )
+        },
+    );
+
+    assert!(matches!(sent, Err(PylonError::Cancelled)));
+    assert!(received.is_err());
+    drop(sender);
+    drop(receiver);
+    let sender_events: Vec<PylonEvent> = smol::block_on(sender_events.collect());
+    let receiver_events: Vec<PylonEvent> = smol::block_on(receiver_events.collect());
+    assert_eq!(
+        kinds(&sender_events),
+        [
+            "code",
+            "peer",
+            "verifier",
+            "transit",
+            "progress",
+            "cancelled"
+        ]
+    );
+    assert_eq!(
+        kinds(&receiver_events),
+        ["peer", "verifier", "transit", "progress", "failed"]
+    );
+}
@@ -0,0 +1,75 @@
+//! Tests for folder transfers.
+
+mod common;
+
+use std::path::Path;
+
+use common::{Progress, Transit};
+use libpylon::PylonState;
+
+/// Returns the paths of all files and folders within a folder, relative to it, along with the contents of the files.
+///
+/// # Arguments
+///
+/// * `folder` - The folder to list.
+fn tree(folder: &Path) -> Vec<(String, Option<Vec<u8>>)> {
+    let mut entries = Vec::new();
+    let mut pending = vec![folder.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.strip_prefix(folder).unwrap().to_string_lossy().into();
+            if path.is_dir() {
+                entries.push((name, None));
+                pending.push(path);
+            } else {
+                entries.push((name, Some(std::fs::read(&path).unwrap())));
+            }
+        }
+    }
+    entries.sort();
+    entries
+}
+
+#[test]
+fn folder_round_trip_preserves_tree() {
+    let url = common::start();
+    let dir = common::work_dir("folder_round_trip_preserves_tree");
+    let folder = dir.join("photos");
+    // Names longer than a tar header can hold are stored as GNU long names.
+    let long_name = "a".repeat(120);
+    std::fs::create_dir_all(folder.join("2023/summer/beach")).unwrap();
+    std::fs::create_dir_all(folder.join("empty/nested-empty")).unwrap();
+    std::fs::write(folder.join("readme.txt"), b"holiday photos").unwrap();
+    std::fs::write(folder.join("2023/zero.bin"), b"").unwrap();
+    std::fs::write(folder.join("2023/summer/beach/sand.jpg"), vec![7; 1500]).unwrap();
+    std::fs::write(folder.join("2023/summer").join(&long_name), b"long").unwrap();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+
+    let (sent, received) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| sender.start_folder_transfer(&folder, None::<Progress>, None::<Transit>, None),
+        |receiver| {
+            receiver.accept_folder_transfer(
+                dir.join("received"),
+                None::<Progress>,
+                None::<Transit>,
+                None,
+            )
+        },
+    );
+
+    let sent = sent.unwrap();
+    let received = received.unwrap().unwrap();
+    assert_eq!(sent.sha256, received.sha256);
+    assert_eq!(received.path, Some(dir.join("received")));
+    assert_eq!(tree(&dir.join("received")), tree(&folder));
+    assert!(dir.join("received/empty/nested-empty").is_dir());
+    assert_eq!(
+        std::fs::read(dir.join("received/2023/zero.bin")).unwrap(),
+        b""
+    );
+    assert_eq!(receiver.state(), PylonState::Done);
+}
@@ -0,0 +1,61 @@
+//! Tests for verifying received files against the checksum sent by the sender over the wormhole.
+
+mod common;
+
+use common::{Progress, Transit};
+use libpylon::{PylonError, PylonState, TransferReport};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+const CONTENTS: &[u8] = b"The quick brown fox jumps over the lazy dog";
+
+/// Receives [`CONTENTS`] from a sender that claims the given checksum, returning the result and the receiver's state.
+///
+/// # Arguments
+///
+/// * `name` - The name of the test.
+/// * `sha256` - The hex-encoded checksum that the sender sends over the wormhole.
+fn receive(name: &str, sha256: String) -> (Result<Option<TransferReport>, PylonError>, PylonState) {
+    let url = common::start();
+    let dir = common::work_dir(name);
+    let mut receiver = common::pylon(&url);
+    let offer = json!({ "file": { "filename": "fox.txt", "filesize": CONTENTS.len() } });
+
+    let received = smol::block_on(async {
+        let (code, send) = common::raw_sender(&url, offer, CONTENTS.into(), Some(sha256)).await;
+        let receive = async {
+            receiver.request_transfer(code, None).await.unwrap();
+            receiver
+                .accept_transfer(dir.join("fox.txt"), None::<Progress>, None::<Transit>, None)
+                .await
+        };
+        smol::future::zip(send, receive).await.1
+    });
+
+    if received.is_err() {
+        assert!(!dir.join("fox.txt").exists());
+        assert!(!dir.join("fox.txt.part").exists());
+    }
+    (received, receiver.state())
+}
+
+#[test]
+fn matching_checksum_is_accepted() {
+    let sha256 = hex::encode(Sha256::digest(CONTENTS));
+    let (received, state) = receive("matching_checksum_is_accepted", sha256.clone());
+
+    assert_eq!(received.unwrap().unwrap().sha256, sha256);
+    assert_eq!(state, PylonState::Done);
+}
+
+#[test]
+fn mismatched_checksum_fails_with_integrity_error() {
+    let sha256 = hex::encode(Sha256::digest(b"something else"));
+    let (received, state) = receive("mismatched_checksum_fails_with_integrity_error", sha256);
+
+    let error = received.unwrap_err();
+    assert!(matches!(error, PylonError::IntegrityError));
+    assert_eq!(error.code(), "integrity");
+    assert!(error.is_retryable());
+    assert_eq!(state, PylonState::Failed);
+}
@@ -0,0 +1,120 @@
+//! Tests for receiving files through a `.part` file, which must not be left behind by unfinished transfers.
+
+mod common;
+
+use std::path::Path;
+
+use common::{Progress, Transit};
+use libpylon::{PylonCancelToken, PylonError, PylonState};
+
+/// The size of the sent file, large enough to be sent in many records.
+const FILE_SIZE: usize = 1024 * 1024;
+
+/// Returns the names of the files within a folder, sorted.
+///
+/// # Arguments
+///
+/// * `dir` - The folder to list.
+fn names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn cancelled_transfer_removes_part_file() {
+    let url = common::start();
+    let dir = common::work_dir("cancelled_transfer_removes_part_file");
+    std::fs::write(dir.join("file.bin"), vec![1; FILE_SIZE]).unwrap();
+    std::fs::create_dir(dir.join("downloads")).unwrap();
+    let mut sender = common::pylon(&url);
+    // Cancelled transfers aren't resumed, so nothing is kept even by a resumable Pylon.
+    let mut receiver = common::builder(&url).resumable(true).build().unwrap();
+    let cancel_token = PylonCancelToken::new();
+    let cancel = cancel_token.clone();
+    let progress = move |received: u64, _| {
+        if received > (FILE_SIZE / 2) as u64 {
+            cancel.cancel();
+        }
+    };
+
+    let (sent, received) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| {
+            sender.start_transfer(
+                dir.join("file.bin"),
+                None::<Progress>,
+                None::<Transit>,
+                None,
+            )
+        },
+        |receiver| {
+            receiver.accept_transfer(
+                dir.join("downloads/file.bin"),
+                Some(progress),
+                None::<Transit>,
+                Some(cancel_token),
+            )
+        },
+    );
+
+    assert!(sent.is_err());
+    assert!(matches!(received, Err(PylonError::Cancelled)));
+    assert!(names(&dir.join("downloads")).is_empty());
+    assert_eq!(receiver.state(), PylonState::Idle);
+}
+
+#[test]
+fn failed_transfer_removes_part_file() {
+    let url = common::start();
+    let dir = common::work_dir("failed_transfer_removes_part_file");
+    std::fs::write(dir.join("file.bin"), vec![1; FILE_SIZE]).unwrap();
+    std::fs::create_dir(dir.join("downloads")).unwrap();
+    std::fs::write(dir.join("downloads/file.bin"), b"old").unwrap();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+    // The sender gives up halfway through, which fails the transfer on the receiver's side.
+    let cancel_token = PylonCancelToken::new();
+    let cancel = cancel_token.clone();
+    let progress = move |sent: u64, _| {
+        if sent > (FILE_SIZE / 2) as u64 {
+            cancel.cancel();
+        }
+    };
+
+    let (sent, received) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| {
+            sender.start_transfer(
+                dir.join("file.bin"),
+                Some(progress),
+                None::<Transit>,
+                Some(cancel_token),
+            )
+        },
+        |receiver| {
+            receiver.accept_transfer(
+                dir.join("downloads/file.bin"),
+                None::<Progress>,
+                None::<Transit>,
+                None,
+            )
+        },
+    );
+
+    assert!(matches!(sent, Err(PylonError::Cancelled)));
+    let error = received.unwrap_err();
+    assert!(!matches!(error, PylonError::Cancelled), "{}", error);
+    // The existing file is only replaced by a complete one.
+    assert_eq!(names(&dir.join("downloads")), ["file.bin"]);
+    assert_eq!(
+        std::fs::read(dir.join("downloads/file.bin")).unwrap(),
+        b"old"
+    );
+    assert_eq!(receiver.state(), PylonState::Failed);
+}
@@ -0,0 +1,140 @@
+//! Tests for receiving names chosen by the sender Pylon, which must not let it write outside of the destination.
+
+mod common;
+
+use async_tar::{EntryType, Header};
+use common::{Progress, Transit};
+use libpylon::PylonError;
+use serde_json::json;
+
+/// Returns a tar archive with a single entry, written as is, without the checks of the archive builder.
+///
+/// # Arguments
+///
+/// * `path` - The path of the entry.
+/// * `entry_type` - The type of the entry.
+/// * `link` - The target of the entry, if it is a link.
+fn archive(path: &str, entry_type: EntryType, link: Option<&str>) -> Vec<u8> {
+    let contents = b"escaped";
+    let mut header = Header::new_gnu();
+    header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+    header.set_entry_type(entry_type);
+    header.set_mode(0o644);
+    match link {
+        Some(link) => {
+            header.as_old_mut().linkname[..link.len()].copy_from_slice(link.as_bytes());
+            header.set_size(0);
+        }
+        None => header.set_size(contents.len() as u64),
+    }
+    header.set_cksum();
+
+    let mut archive = header.as_bytes().to_vec();
+    if link.is_none() {
+        archive.extend_from_slice(contents);
+        archive.resize(1024, 0);
+    }
+    archive.resize(archive.len() + 1024, 0);
+    archive
+}
+
+#[test]
+fn offered_names_are_sanitized() {
+    let url = common::start();
+    let dir = common::work_dir("offered_names_are_sanitized");
+    let downloads = dir.join("downloads");
+    let absolute = dir.join("absolute.txt");
+    let names = [
+        ("../../escaped.txt", "escaped.txt"),
+        (absolute.to_str().unwrap(), "absolute.txt"),
+        ("..\\..\\windows.txt", "windows.txt"),
+        ("CON", "_CON"),
+        ("com0.txt", "_com0.txt"),
+        ("LPT0", "_LPT0"),
+        ("CONIN$", "_CONIN$"),
+        ("CONOUT$.log", "_CONOUT$.log"),
+    ];
+
+    for (name, sanitized) in names {
+        let mut sender = common::pylon(&url);
+        let mut receiver = common::pylon(&url);
+        let source = &mut smol::io::Cursor::new(name.as_bytes().to_vec());
+
+        let (sent, received) = common::connect(
+            &mut sender,
+            &mut receiver,
+            move |sender| {
+                sender.start_transfer_from(
+                    source,
+                    name,
+                    name.len() as u64,
+                    None::<Progress>,
+                    None::<Transit>,
+                    None,
+                )
+            },
+            |receiver| {
+                receiver.accept_transfer_to_dir(&downloads, None::<Progress>, None::<Transit>, None)
+            },
+        );
+
+        sent.unwrap();
+        let received = received.unwrap().unwrap();
+        assert_eq!(received.path, Some(downloads.join(sanitized)), "{}", name);
+        assert_eq!(
+            std::fs::read(downloads.join(sanitized)).unwrap(),
+            name.as_bytes()
+        );
+    }
+    assert!(!dir.join("escaped.txt").exists());
+    assert!(!absolute.exists());
+    assert_eq!(std::fs::read_dir(&downloads).unwrap().count(), names.len());
+}
+
+#[test]
+fn archives_leaving_the_folder_are_rejected() {
+    let url = common::start();
+    let dir = common::work_dir("archives_leaving_the_folder_are_rejected");
+    let absolute = dir.join("absolute.txt");
+    let archives = [
+        archive("../escaped.txt", EntryType::Regular, None),
+        archive("nested/../../escaped.txt", EntryType::Regular, None),
+        archive(absolute.to_str().unwrap(), EntryType::Regular, None),
+        archive("symlink", EntryType::Symlink, Some("/etc/passwd")),
+        archive("hardlink", EntryType::Link, Some("/etc/passwd")),
+    ];
+
+    for (i, archive) in archives.into_iter().enumerate() {
+        let mut receiver = common::pylon(&url);
+        let folder = dir.join("received").join(i.to_string());
+        let offer = json!({
+            "directory": {
+                "dirname": "folder",
+                "mode": "tar",
+                "zipsize": archive.len(),
+                "numbytes": 7,
+                "numfiles": 1,
+            }
+        });
+
+        let received = smol::block_on(async {
+            let (code, send) = common::raw_sender(&url, offer, archive, None).await;
+            let receive = async {
+                receiver.request_transfer(code, None).await.unwrap();
+                receiver
+                    .accept_folder_transfer(&folder, None::<Progress>, None::<Transit>, None)
+                    .await
+            };
+            smol::future::zip(send, receive).await.1
+        });
+
+        let error = received.unwrap_err();
+        assert!(matches!(error, PylonError::PathTraversal(_)), "{}", error);
+        assert_eq!(error.code(), "path_traversal");
+        assert!(!folder.join("symlink").exists());
+        assert!(!folder.join("hardlink").exists());
+    }
+    assert!(!dir.join("escaped.txt").exists());
+    assert!(!dir.join("received/escaped.txt").exists());
+    assert!(!absolute.exists());
+}
@@ -0,0 +1,172 @@
+//! Tests for configuring multiple relay servers.
+
+mod common;
+
+use common::{Progress, Transit};
+use libpylon::{
+    Abilities, PylonBuilder, PylonError, PylonState, Relay, TransferReport, TransitInfo,
+};
+
+const CONTENTS: &[u8] = b"The quick brown fox jumps over the lazy dog";
+
+/// Returns a relay server with the given name and URLs.
+///
+/// # Arguments
+///
+/// * `name` - The name of the relay server.
+/// * `urls` - The URLs of the relay server.
+fn relay(name: &str, urls: &[&str]) -> Relay {
+    Relay {
+        name: Some(name.into()),
+        urls: urls.iter().map(|&url| url.into()).collect(),
+    }
+}
+
+#[test]
+fn transfer_offers_all_relays() {
+    let url = common::start();
+    let dir = common::work_dir("transfer_offers_all_relays");
+    std::fs::write(dir.join("fox.txt"), CONTENTS).unwrap();
+    let mut sender = common::builder(&url)
+        .relay(relay("eu", &["tcp://127.0.0.1:9", "ws://127.0.0.1:9"]))
+        .relay(relay("us", &["tcp://127.0.0.1:9"]))
+        .build()
+        .unwrap();
+    let mut receiver = common::pylon(&url);
+
+    let (sent, received) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| {
+            sender.start_transfer(dir.join("fox.txt"), None::<Progress>, None::<Transit>, None)
+        },
+        |receiver| {
+            receiver.accept_transfer(
+                dir.join("received.txt"),
+                None::<Progress>,
+                None::<Transit>,
+                None,
+            )
+        },
+    );
+
+    assert_eq!(sent.unwrap().sha256, received.unwrap().unwrap().sha256);
+    assert_eq!(std::fs::read(dir.join("received.txt")).unwrap(), CONTENTS);
+}
+
+/// Sends a file between Pylons built from the given builders, returning the reports of both sides.
+///
+/// # Arguments
+///
+/// * `name` - The name of the test.
+/// * `sender` - The builder of the sender Pylon.
+/// * `receiver` - The builder of the receiver Pylon.
+fn transfer(
+    name: &str,
+    sender: &PylonBuilder,
+    receiver: &PylonBuilder,
+) -> (TransferReport, TransferReport) {
+    let dir = common::work_dir(name);
+    std::fs::write(dir.join("fox.txt"), CONTENTS).unwrap();
+    let mut sender = sender.build().unwrap();
+    let mut receiver = receiver.build().unwrap();
+
+    let (sent, received) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| {
+            sender.start_transfer(dir.join("fox.txt"), None::<Progress>, None::<Transit>, None)
+        },
+        |receiver| {
+            receiver.accept_transfer(
+                dir.join("received.txt"),
+                None::<Progress>,
+                None::<Transit>,
+                None,
+            )
+        },
+    );
+
+    assert_eq!(std::fs::read(dir.join("received.txt")).unwrap(), CONTENTS);
+    (sent.unwrap(), received.unwrap().unwrap())
+}
+
+#[test]
+fn relay_only_transfer_goes_through_configured_relay() {
+    let url = common::start();
+    let relay_url = common::start_relay();
+    // Only the sender knows about the relay server, and neither side accepts direct connections.
+    let mut sender = common::builder(&url);
+    sender
+        .abilities(Abilities::FORCE_RELAY)
+        .relay(relay("live", &[&relay_url]));
+    let mut receiver = common::builder(&url);
+    receiver.abilities(Abilities::FORCE_RELAY);
+
+    let (sent, received) = transfer(
+        "relay_only_transfer_goes_through_configured_relay",
+        &sender,
+        &receiver,
+    );
+
+    for report in [sent, received] {
+        assert!(matches!(
+            report.peer.transit,
+            TransitInfo::Relay { name: Some(name) } if name == "live"
+        ));
+    }
+}
+
+#[test]
+fn relay_url_is_offered_before_configured_relays() {
+    let url = common::start();
+    let relay_url = common::start_relay();
+    // The peer only tries the first two relay servers offered, none of which are reachable here but the relay URL.
+    let mut sender = common::builder(&url);
+    sender
+        .abilities(Abilities::FORCE_RELAY)
+        .relay_url(relay_url)
+        .relay(relay("eu", &[common::RELAY_URL]))
+        .relay(relay("us", &[common::RELAY_URL]));
+    let mut receiver = common::builder(&url);
+    receiver.abilities(Abilities::FORCE_RELAY);
+
+    let (sent, received) = transfer(
+        "relay_url_is_offered_before_configured_relays",
+        &sender,
+        &receiver,
+    );
+
+    for report in [sent, received] {
+        assert!(matches!(
+            report.peer.transit,
+            TransitInfo::Relay { name: None }
+        ));
+    }
+}
+
+#[test]
+fn relay_without_urls_fails() {
+    let url = common::start();
+    let mut sender = common::builder(&url)
+        .relay(relay("eu", &[]))
+        .build()
+        .unwrap();
+
+    let sent = smol::block_on(async {
+        sender.gen_code(2).await.unwrap();
+        sender
+            .start_transfer_from(
+                &mut smol::io::Cursor::new(CONTENTS),
+                "fox.txt",
+                CONTENTS.len() as u64,
+                None::<Progress>,
+                None::<Transit>,
+                None,
+            )
+            .await
+    });
+
+    assert!(matches!(sent, Err(PylonError::InvalidRelay(name)) if &*name == "eu"));
+    assert_eq!(sender.state(), PylonState::CodeGenerated);
+}
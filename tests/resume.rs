@@ -0,0 +1,106 @@
+//! Tests for resuming interrupted file transfers from the `.part` file and resume record they left behind.
+
+mod common;
+
+use std::path::Path;
+
+use common::{Progress, Transit};
+use libpylon::{PylonCancelToken, PylonError, TransferReport};
+
+/// The size of the sent file, large enough to be sent in many records.
+const FILE_SIZE: usize = 1024 * 1024;
+
+/// Sends the file `file.bin` of the given folder to a resumable Pylon, which receives it into `downloads/file.bin`.
+///
+/// # Arguments
+///
+/// * `url` - The URL of the rendezvous server.
+/// * `dir` - The folder to work in.
+/// * `interrupt` - Whether the sender gives up halfway through.
+fn transfer(url: &str, dir: &Path, interrupt: bool) -> Result<Option<TransferReport>, PylonError> {
+    let mut sender = common::pylon(url);
+    let mut receiver = common::builder(url).resumable(true).build().unwrap();
+    let cancel_token = PylonCancelToken::new();
+    let cancel = cancel_token.clone();
+    let progress = move |sent: u64, _| {
+        if interrupt && sent > (FILE_SIZE / 2) as u64 {
+            cancel.cancel();
+        }
+    };
+
+    common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| {
+            sender.start_transfer(
+                dir.join("file.bin"),
+                Some(progress),
+                None::<Transit>,
+                Some(cancel_token),
+            )
+        },
+        |receiver| {
+            receiver.accept_transfer(
+                dir.join("downloads/file.bin"),
+                None::<Progress>,
+                None::<Transit>,
+                None,
+            )
+        },
+    )
+    .1
+}
+
+/// Returns the contents of the sent file, which differ from record to record so that misplaced data is noticed.
+fn contents() -> Vec<u8> {
+    (0..FILE_SIZE).map(|i| (i % 251) as u8).collect()
+}
+
+#[test]
+fn interrupted_transfer_resumes_from_part_file() {
+    let url = common::start();
+    let dir = common::work_dir("interrupted_transfer_resumes_from_part_file");
+    std::fs::write(dir.join("file.bin"), contents()).unwrap();
+    std::fs::create_dir(dir.join("downloads")).unwrap();
+    let part = dir.join("downloads/file.bin.part");
+    let record = dir.join("downloads/file.bin.part.resume");
+
+    assert!(transfer(&url, &dir, true).is_err());
+    let offset = std::fs::metadata(&part).unwrap().len();
+    assert!(offset > 0 && offset < FILE_SIZE as u64);
+    assert!(record.exists());
+    assert!(!dir.join("downloads/file.bin").exists());
+
+    let received = transfer(&url, &dir, false).unwrap().unwrap();
+    assert_eq!(received.bytes, FILE_SIZE as u64 - offset);
+    assert_eq!(
+        std::fs::read(dir.join("downloads/file.bin")).unwrap(),
+        contents()
+    );
+    assert!(!part.exists());
+    assert!(!record.exists());
+}
+
+#[test]
+fn changed_part_file_starts_over() {
+    let url = common::start();
+    let dir = common::work_dir("changed_part_file_starts_over");
+    std::fs::write(dir.join("file.bin"), contents()).unwrap();
+    std::fs::create_dir(dir.join("downloads")).unwrap();
+    let part = dir.join("downloads/file.bin.part");
+
+    assert!(transfer(&url, &dir, true).is_err());
+    // The `.part` file no longer matches its resume record.
+    let mut changed = std::fs::read(&part).unwrap();
+    changed[0] ^= 0xff;
+    std::fs::write(&part, changed).unwrap();
+
+    let received = transfer(&url, &dir, false).unwrap().unwrap();
+    assert_eq!(received.bytes, FILE_SIZE as u64);
+    assert_eq!(
+        std::fs::read(dir.join("downloads/file.bin")).unwrap(),
+        contents()
+    );
+    assert!(!part.exists());
+    assert!(!dir.join("downloads/file.bin.part.resume").exists());
+}
@@ -0,0 +1,96 @@
+//! Tests for retrying failed connection attempts.
+
+mod common;
+
+use std::time::Duration;
+
+use libpylon::{PylonEvent, PylonState, RetryPolicy, TransferPhase};
+use smol::stream::StreamExt;
+
+/// A rendezvous server that nobody listens on.
+const UNREACHABLE_URL: &str = "ws://127.0.0.1:9/v1";
+
+fn policy(max_attempts: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_attempts,
+        initial_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(20),
+        jitter: false,
+        retry_on: None,
+    }
+}
+
+#[test]
+fn gen_code_retries_rendezvous_connection() {
+    let mut pylon = common::builder(UNREACHABLE_URL)
+        .retry_policy(policy(3))
+        .build()
+        .unwrap();
+    let events = pylon.events();
+
+    let error = smol::block_on(pylon.gen_code(2)).unwrap_err();
+    drop(pylon);
+    let events: Vec<PylonEvent> = smol::block_on(events.collect());
+
+    assert_eq!(error.code(), "rendezvous_failed");
+    let retries: Vec<(TransferPhase, u32, Duration)> = events
+        .iter()
+        .filter_map(|event| match event {
+            PylonEvent::Retrying {
+                phase,
+                attempt,
+                delay,
+                ..
+            } => Some((*phase, *attempt, *delay)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        retries,
+        vec![
+            (TransferPhase::Rendezvous, 1, Duration::from_millis(10)),
+            (TransferPhase::Rendezvous, 2, Duration::from_millis(20)),
+        ]
+    );
+    assert!(matches!(events.last(), Some(PylonEvent::Failed { .. })));
+}
+
+#[test]
+fn retry_policy_only_retries_listed_codes() {
+    let mut pylon = common::builder(UNREACHABLE_URL)
+        .retry_policy(RetryPolicy {
+            retry_on: Some(vec!["io".into()]),
+            ..policy(3)
+        })
+        .build()
+        .unwrap();
+    let events = pylon.events();
+
+    smol::block_on(pylon.gen_code(2)).unwrap_err();
+    drop(pylon);
+    let events: Vec<PylonEvent> = smol::block_on(events.collect());
+
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event, PylonEvent::Retrying { .. })));
+}
+
+#[test]
+fn gen_code_does_not_retry_on_success() {
+    let url = common::start();
+    let mut sender = common::builder(&url)
+        .retry_policy(policy(3))
+        .build()
+        .unwrap();
+    let events = sender.events();
+
+    smol::block_on(sender.gen_code(2)).unwrap();
+    assert_eq!(sender.state(), PylonState::CodeGenerated);
+    drop(sender);
+    let events: Vec<PylonEvent> = smol::block_on(events.collect());
+
+    assert!(matches!(
+        events.as_slice(),
+        [PylonEvent::CodeAllocated { .. }]
+    ));
+}
@@ -0,0 +1,26 @@
+//! Tests for text messages.
+
+mod common;
+
+use libpylon::PylonState;
+
+#[test]
+fn text_round_trip() {
+    let url = common::start();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+    let text = "Grüße from the other side 👋\nwith a second line";
+
+    let (sent, received) = smol::block_on(async {
+        let code = sender.gen_code(2).await.unwrap();
+        let send = sender.send_text(text, None);
+        let receive = receiver.request_transfer(code, None);
+        smol::future::zip(send, receive).await
+    });
+
+    sent.unwrap();
+    assert_eq!(received.unwrap().as_deref(), Some(text));
+    assert!(receiver.pending_offer().is_none());
+    assert_eq!(sender.state(), PylonState::Done);
+    assert_eq!(receiver.state(), PylonState::Done);
+}
@@ -0,0 +1,75 @@
+//! Tests for the per-phase timeouts.
+
+mod common;
+
+use std::net::TcpListener;
+use std::time::Duration;
+
+use common::{Progress, Transit};
+use libpylon::{PylonError, PylonState, RetryPolicy, Timeouts, TransferPhase};
+
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+#[test]
+fn rendezvous_times_out() {
+    // A server that accepts connections but never answers.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("ws://{}/v1", listener.local_addr().unwrap());
+    let mut pylon = common::builder(&url)
+        .retry_policy(RetryPolicy::none())
+        .timeouts(Timeouts {
+            rendezvous: Some(TIMEOUT),
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+
+    let generated = smol::block_on(pylon.gen_code(2));
+
+    assert!(matches!(
+        generated,
+        Err(PylonError::Timeout {
+            phase: TransferPhase::Rendezvous
+        })
+    ));
+    assert_eq!(pylon.state(), PylonState::Failed);
+}
+
+#[test]
+fn waiting_for_receiver_times_out() {
+    let url = common::start();
+    let mut sender = common::builder(&url)
+        .timeouts(Timeouts {
+            peer_wait: Some(TIMEOUT),
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+
+    let sent = smol::block_on(async {
+        sender.gen_code(2).await.unwrap();
+        sender
+            .start_transfer_from(
+                &mut smol::io::Cursor::new(b"unsent"),
+                "unsent.txt",
+                6,
+                None::<Progress>,
+                None::<Transit>,
+                None,
+            )
+            .await
+    });
+
+    let error = sent.unwrap_err();
+    assert!(matches!(
+        error,
+        PylonError::Timeout {
+            phase: TransferPhase::PeerWait
+        }
+    ));
+    assert_eq!(error.code(), "timeout");
+    assert!(!error.is_retryable());
+    // The abandoned handshake is dropped, so the Pylon can start over.
+    assert_eq!(sender.state(), PylonState::Failed);
+    assert!(smol::block_on(sender.gen_code(2)).is_ok());
+}
@@ -0,0 +1,183 @@
+//! Regression tests for the results of accepting and rejecting transfers.
+
+mod common;
+
+use common::{Progress, Transit};
+use libpylon::{OfferKind, PylonError, PylonState, TransitInfo};
+
+const CONTENTS: &[u8] = b"The quick brown fox jumps over the lazy dog";
+
+#[test]
+fn accept_transfer_returns_report() {
+    let url = common::start();
+    let dir = common::work_dir("accept_transfer_returns_report");
+    std::fs::write(dir.join("fox.txt"), CONTENTS).unwrap();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+
+    let (sent, received) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| {
+            sender.start_transfer(dir.join("fox.txt"), None::<Progress>, None::<Transit>, None)
+        },
+        |receiver| {
+            receiver.accept_transfer(
+                dir.join("received.txt"),
+                None::<Progress>,
+                None::<Transit>,
+                None,
+            )
+        },
+    );
+
+    let sent = sent.unwrap();
+    let received = received.unwrap().unwrap();
+    assert_eq!(sent.path, None);
+    assert_eq!(received.path, Some(dir.join("received.txt")));
+    assert_eq!(sent.sha256, received.sha256);
+    assert_eq!(sent.bytes, CONTENTS.len() as u64);
+    assert_eq!(received.bytes, CONTENTS.len() as u64);
+    assert!(matches!(sent.peer.transit, TransitInfo::Direct));
+    assert!(matches!(received.peer.transit, TransitInfo::Direct));
+    assert_eq!(std::fs::read(dir.join("received.txt")).unwrap(), CONTENTS);
+    assert_eq!(sender.state(), PylonState::Done);
+    assert_eq!(receiver.state(), PylonState::Done);
+}
+
+#[test]
+fn accept_transfer_into_returns_report() {
+    let url = common::start();
+    let dir = common::work_dir("accept_transfer_into_returns_report");
+    std::fs::write(dir.join("fox.txt"), CONTENTS).unwrap();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+    let mut sink = Vec::new();
+    let into = &mut sink;
+
+    let (sent, received) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| {
+            sender.start_transfer(dir.join("fox.txt"), None::<Progress>, None::<Transit>, None)
+        },
+        move |receiver| {
+            receiver.accept_transfer_into(into, None::<Progress>, None::<Transit>, None)
+        },
+    );
+
+    let received = received.unwrap();
+    assert_eq!(received.path, None);
+    assert_eq!(received.sha256, sent.unwrap().sha256);
+    assert_eq!(received.bytes, CONTENTS.len() as u64);
+    assert_eq!(sink, CONTENTS);
+}
+
+#[test]
+fn reject_transfer_returns_offer() {
+    let url = common::start();
+    let dir = common::work_dir("reject_transfer_returns_offer");
+    std::fs::write(dir.join("fox.txt"), CONTENTS).unwrap();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+
+    let (sent, rejected) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| {
+            sender.start_transfer(dir.join("fox.txt"), None::<Progress>, None::<Transit>, None)
+        },
+        |receiver| receiver.reject_transfer(),
+    );
+
+    let offer = rejected.unwrap();
+    assert_eq!(offer.name, "fox.txt");
+    assert_eq!(offer.size, CONTENTS.len() as u64);
+    assert_eq!(offer.kind, OfferKind::File);
+    assert!(matches!(sent, Err(PylonError::Rejected)));
+    assert_eq!(receiver.state(), PylonState::Idle);
+    assert!(receiver.pending_offer().is_none());
+    assert_eq!(sender.state(), PylonState::Failed);
+    assert!(!dir.join("received.txt").exists());
+}
+
+#[test]
+fn reject_transfer_without_offer_fails() {
+    let mut receiver = common::pylon(&common::start());
+
+    let rejected = smol::block_on(receiver.reject_transfer());
+
+    assert!(matches!(
+        rejected,
+        Err(PylonError::InvalidState {
+            expected: PylonState::OfferPending,
+            actual: PylonState::Idle,
+        })
+    ));
+}
+
+#[test]
+fn errors_serialize_as_payloads() {
+    let mut receiver = common::pylon(&common::start());
+
+    let error = smol::block_on(receiver.reject_transfer()).unwrap_err();
+    let payload = serde_json::to_value(&error).unwrap();
+
+    assert_eq!(payload["code"], "invalid_state");
+    assert_eq!(payload["message"], error.to_string());
+    assert_eq!(payload["retryable"], false);
+    assert_eq!(payload["sources"], serde_json::json!([]));
+    assert_eq!(PylonError::Rejected.code(), "peer_rejected");
+    assert_eq!(PylonError::Cancelled.code(), "cancelled");
+}
+
+#[test]
+fn folder_offers_are_not_accepted_as_files() {
+    let url = common::start();
+    let dir = common::work_dir("folder_offers_are_not_accepted_as_files");
+    std::fs::create_dir(dir.join("folder")).unwrap();
+    std::fs::write(dir.join("folder/fox.txt"), CONTENTS).unwrap();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+    let mut sink = Vec::new();
+    let into = &mut sink;
+    let tar = dir.join("received.tar");
+
+    let (sent, (accepted, accepted_into)) = common::connect(
+        &mut sender,
+        &mut receiver,
+        |sender| {
+            sender.start_folder_transfer(
+                dir.join("folder"),
+                None::<Progress>,
+                None::<Transit>,
+                None,
+            )
+        },
+        move |receiver| async move {
+            let accepted = receiver
+                .accept_transfer(tar, None::<Progress>, None::<Transit>, None)
+                .await;
+            let accepted_into = receiver
+                .accept_transfer_into(into, None::<Progress>, None::<Transit>, None)
+                .await;
+            assert_eq!(receiver.state(), PylonState::OfferPending);
+            receiver.reject_transfer().await.unwrap();
+            (accepted, accepted_into)
+        },
+    );
+
+    for error in [accepted.unwrap_err(), accepted_into.unwrap_err()] {
+        assert!(matches!(
+            error,
+            PylonError::UnexpectedOffer {
+                expected: OfferKind::File,
+                actual: OfferKind::Folder,
+            }
+        ));
+    }
+    assert!(sink.is_empty());
+    assert!(!dir.join("received.tar").exists());
+    assert!(!dir.join("received.tar.part").exists());
+    assert!(matches!(sent, Err(PylonError::Rejected)));
+}
@@ -0,0 +1,59 @@
+//! Tests for `wormhole-transfer:` URIs and their QR codes.
+
+mod common;
+
+use libpylon::uri::{self, CodeUri};
+use libpylon::Abilities;
+
+#[test]
+fn default_settings_are_left_out() {
+    let pylon = libpylon::PylonBuilder::default()
+        .id("lothar.com/wormhole/text-or-file-xfer".into())
+        .build()
+        .unwrap();
+
+    let uri = pylon.code_uri("4-hurricane-equipment");
+
+    assert_eq!(uri.to_string(), "wormhole-transfer:4-hurricane-equipment");
+    assert_eq!(uri.to_string().parse::<CodeUri>().unwrap(), uri);
+}
+
+#[test]
+fn parsed_uri_configures_receiver() {
+    let url = common::start();
+    let mut sender = common::pylon(&url);
+
+    let (sent, received) = smol::block_on(async {
+        let code = sender.gen_code(2).await.unwrap();
+        let uri = sender.code_uri(&code).to_string();
+        assert!(uri.contains("appid=test.pylon%2Flibpylon"));
+
+        let (mut builder, code) = uri::parse(&uri).unwrap();
+        let mut receiver = builder.abilities(Abilities::FORCE_DIRECT).build().unwrap();
+        let send = sender.send_text("hello", None);
+        let receive = receiver.request_transfer(code, None);
+        smol::future::zip(send, receive).await
+    });
+
+    sent.unwrap();
+    assert_eq!(received.unwrap(), Some("hello".into()));
+}
+
+#[test]
+fn receiver_generated_uris_are_refused() {
+    let error = "wormhole-transfer:4-hurricane-equipment?role=leader"
+        .parse::<CodeUri>()
+        .unwrap_err();
+
+    assert_eq!(error.code(), "uri_invalid");
+}
+
+#[test]
+fn uri_renders_as_qr_code() {
+    let uri: CodeUri = "wormhole-transfer:4-hurricane-equipment".parse().unwrap();
+
+    assert!(uri.to_svg().unwrap().contains("<svg"));
+    let png = uri.to_png(4).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert!(uri.to_terminal().unwrap().contains('█'));
+}
@@ -0,0 +1,152 @@
+//! Tests for the verifiers of wormholes and for confirming them before anything is transferred.
+
+mod common;
+
+use std::time::Duration;
+
+use libpylon::verifier::Verifier;
+use libpylon::{PylonConfirmToken, PylonError, PylonEvent, PylonState};
+use smol::stream::StreamExt;
+
+/// Waits until a verifier awaits confirmation through the token, then confirms or rejects it and returns it.
+///
+/// # Arguments
+///
+/// * `token` - The token of the Pylon.
+/// * `confirmed` - Whether to confirm the verifier.
+async fn decide(token: &PylonConfirmToken, confirmed: bool) -> Verifier {
+    loop {
+        if let Some(verifier) = token.verifier() {
+            match confirmed {
+                true => token.confirm(),
+                false => token.reject(),
+            }
+            return verifier;
+        }
+        smol::Timer::after(Duration::from_millis(10)).await;
+    }
+}
+
+#[test]
+fn pylons_share_verifier() {
+    let url = common::start();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::pylon(&url);
+    let events = sender.events();
+
+    let (sent, received) = smol::block_on(async {
+        let code = sender.gen_code(2).await.unwrap();
+        let send = sender.send_text("hello", None);
+        let receive = receiver.request_transfer(code, None);
+        smol::future::zip(send, receive).await
+    });
+
+    sent.unwrap();
+    received.unwrap();
+    let verifier = sender.verifier().unwrap().clone();
+    assert_eq!(Some(&verifier), receiver.verifier());
+    assert_eq!(verifier.emoji.len(), 7);
+    assert_eq!(verifier.words.len(), 5);
+    drop(sender);
+    let events: Vec<PylonEvent> = smol::block_on(events.collect());
+    assert!(events.iter().any(|event| matches!(
+        event,
+        PylonEvent::VerifierReady { verifier: v } if *v == verifier
+    )));
+}
+
+#[test]
+fn confirmed_verifiers_let_transfer_go_on() {
+    let url = common::start();
+    let sender_token = PylonConfirmToken::new();
+    let receiver_token = PylonConfirmToken::new();
+    let mut sender = common::builder(&url)
+        .confirm_token(Some(sender_token.clone()))
+        .build()
+        .unwrap();
+    let mut receiver = common::builder(&url)
+        .confirm_token(Some(receiver_token.clone()))
+        .build()
+        .unwrap();
+
+    // A verifier can't be confirmed before it is known.
+    sender_token.confirm();
+    let ((sent, received), (sender_verifier, receiver_verifier)) = smol::block_on(async {
+        let code = sender.gen_code(2).await.unwrap();
+        let send = sender.send_text("hello", None);
+        let receive = receiver.request_transfer(code, None);
+        let transfer = smol::future::zip(send, receive);
+        let decide = smol::future::zip(decide(&sender_token, true), decide(&receiver_token, true));
+        smol::future::zip(transfer, decide).await
+    });
+
+    sent.unwrap();
+    assert_eq!(received.unwrap(), Some("hello".into()));
+    assert_eq!(sender_verifier, receiver_verifier);
+    assert_eq!(sender_token.verifier(), None);
+}
+
+#[test]
+fn rejected_verifier_fails_both_pylons() {
+    let url = common::start();
+    let receiver_token = PylonConfirmToken::new();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::builder(&url)
+        .confirm_token(Some(receiver_token.clone()))
+        .build()
+        .unwrap();
+
+    let ((sent, received), _) = smol::block_on(async {
+        let code = sender.gen_code(2).await.unwrap();
+        let send = sender.send_text("unsent", None);
+        let receive = receiver.request_transfer(code, None);
+        let transfer = smol::future::zip(send, receive);
+        smol::future::zip(transfer, decide(&receiver_token, false)).await
+    });
+
+    let error = received.unwrap_err();
+    assert!(matches!(error, PylonError::VerifierRejected));
+    assert_eq!(error.code(), "verifier_rejected");
+    assert!(matches!(sent, Err(PylonError::VerifierRejected)));
+    assert_eq!(sender.state(), PylonState::Failed);
+    assert_eq!(receiver.state(), PylonState::Failed);
+}
+
+#[test]
+fn verifier_can_be_confirmed_from_its_event() {
+    let url = common::start();
+    let receiver_token = PylonConfirmToken::new();
+    let mut sender = common::pylon(&url);
+    let mut receiver = common::builder(&url)
+        .confirm_token(Some(receiver_token.clone()))
+        .build()
+        .unwrap();
+    let mut events = receiver.events();
+    let consumer = std::thread::spawn(move || {
+        smol::block_on(async {
+            while let Some(event) = events.next().await {
+                if let PylonEvent::VerifierReady { verifier } = event {
+                    assert_eq!(receiver_token.verifier(), Some(verifier));
+                    receiver_token.confirm();
+                }
+            }
+        })
+    });
+
+    let (sent, received) = smol::block_on(async {
+        let code = sender.gen_code(2).await.unwrap();
+        let send = sender.send_text("hello", None);
+        let receive = receiver.request_transfer(code, None);
+        let transfer = smol::future::zip(send, receive);
+        smol::future::or(transfer, async {
+            smol::Timer::after(Duration::from_secs(10)).await;
+            panic!("the confirmation was lost");
+        })
+        .await
+    });
+
+    sent.unwrap();
+    assert_eq!(received.unwrap(), Some("hello".into()));
+    drop(receiver);
+    consumer.join().unwrap();
+}